@@ -4,6 +4,7 @@ use criterion::criterion_group;
 use criterion::criterion_main;
 use z157::Tree;
 
+#[allow(clippy::missing_panics_doc)]
 pub fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("parse_large_input", |b| {
         b.iter(|| Tree::parse(black_box(include_str!("inputs/large-input.txt"))));
@@ -12,6 +13,43 @@ pub fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("parse_small_input", |b| {
         b.iter(|| Tree::parse(black_box(include_str!("inputs/small-input.txt"))));
     });
+
+    let large = Tree::parse(include_str!("inputs/large-input.txt")).unwrap();
+
+    c.bench_function("walk_large_input", |b| {
+        b.iter(|| {
+            black_box(&large)
+                .walk()
+                .map(|field| field.name().len())
+                .sum::<usize>()
+        });
+    });
+
+    c.bench_function("leaves_large_input", |b| {
+        b.iter(|| {
+            black_box(&large)
+                .leaves()
+                .map(|field| field.name().len())
+                .sum::<usize>()
+        });
+    });
+
+    c.bench_function("children_large_input", |b| {
+        b.iter(|| {
+            black_box(&large)
+                .walk()
+                .map(|field| field.children().count())
+                .sum::<usize>()
+        });
+    });
+
+    c.bench_function("index_large_input", |b| {
+        b.iter(|| {
+            black_box(&large).index(black_box([
+                "narrow", "pathetic", "icky", "homeless", "tiny", "erratic", "page", "glib",
+            ]))
+        });
+    });
 }
 
 criterion_group!(
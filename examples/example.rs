@@ -25,47 +25,9 @@ fn main() {
     let fields = std::env::args()
         .nth(1)
         .map(|fields| Tree::parse(fields).unwrap());
+    let mut user = serde_json::to_value(user).unwrap();
     if let Some(fields) = fields {
-        let mut user = serde_json::to_value(user).unwrap();
-        if fields.negation() {
-            for field in fields.leaves() {
-                let mut remove = user.as_object_mut().unwrap();
-                let path = field.path();
-                for &part in path.iter().take(path.len() - 1) {
-                    remove = remove.get_mut(part).unwrap().as_object_mut().unwrap();
-                }
-                remove.remove(*path.last().unwrap()).unwrap();
-            }
-        } else {
-            let mut out = serde_json::Map::new();
-            for field in fields.leaves() {
-                let mut insert = &mut out;
-                let mut user_field_container = user.as_object_mut().unwrap();
-                let path = field.path();
-                for &part in path.iter().take(path.len() - 1) {
-                    insert = insert
-                        .entry(part)
-                        .or_insert(serde_json::Value::Object(serde_json::Map::new()))
-                        .as_object_mut()
-                        .unwrap();
-                    user_field_container = user_field_container
-                        .get_mut(part)
-                        .unwrap()
-                        .as_object_mut()
-                        .unwrap();
-                }
-                insert.insert(
-                    (*path.last().unwrap()).to_string(),
-                    user_field_container
-                        .get(*path.last().unwrap())
-                        .unwrap()
-                        .clone(),
-                );
-            }
-            user = serde_json::Value::Object(out);
-        }
-        println!("{}", serde_json::to_string_pretty(&user).unwrap());
-    } else {
-        println!("{}", serde_json::to_string_pretty(&user).unwrap());
+        z157::json::apply(&fields, &mut user);
     }
+    println!("{}", serde_json::to_string_pretty(&user).unwrap());
 }
@@ -9,6 +9,10 @@ fuzz_target!(|data: &[u8]| -> Corpus {
         return Corpus::Reject;
     };
     let result = Tree::parse(s);
+    assert_eq!(
+        z157::events(s).all(|event| event.is_ok()),
+        result.is_ok()
+    );
     match result {
         Ok(tree) => {
             for field in tree.walk() {
@@ -17,6 +21,8 @@ fuzz_target!(|data: &[u8]| -> Corpus {
                 let _ = field.parent();
                 let _ = field.children();
                 let _ = field.path();
+                let _ = field.path_iter().count();
+                let _ = field.depth();
             }
             for field in tree.top() {
                 let _ = field.walk();
@@ -0,0 +1,264 @@
+//! This module contains the [`Automaton`], a flat representation of a [`Tree`]
+//! which is cheap to match against repeatedly.
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::tree::Decision;
+use crate::tree::Field;
+use crate::tree::Tree;
+use crate::tree::to_u32;
+
+/// The state reached after a key which is selected along with everything below
+/// it.
+const INCLUDE: StateId = StateId(0);
+/// The state reached after a key which is filtered out along with everything
+/// below it.
+const EXCLUDE: StateId = StateId(1);
+/// The state at the top level of a document.
+const START: StateId = StateId(2);
+
+/// A position in an [`Automaton`].
+///
+/// Only meaningful for the `Automaton` which produced it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct StateId(u32);
+
+/// A [`Tree`] compiled into a flat table of states and transitions.
+///
+/// Every state corresponds to a [`Partial`](Decision::Partial) position in the
+/// tree, except for two final states shared by all selected and all filtered
+/// out positions. The transitions of a state are sorted by key and stored next
+/// to each other, so a step is a binary search over a small slice rather than
+/// a walk over the nodes of a `Tree`.
+///
+/// The `Automaton` owns its data and can be kept around independently of the
+/// `Tree` it was compiled from.
+///
+/// # Example
+///
+/// ```
+/// use z157::Decision;
+/// use z157::Tree;
+///
+/// let tree = Tree::parse("(name,address(city))").unwrap();
+/// let automaton = tree.compile();
+///
+/// let address =
+///     automaton.step(automaton.start(), "address");
+/// assert_eq!(
+///     automaton.decision(address),
+///     Decision::Partial
+/// );
+/// let city = automaton.step(address, "city");
+/// assert_eq!(automaton.decision(city), Decision::Include);
+/// let zip = automaton.step(address, "zip");
+/// assert_eq!(automaton.decision(zip), Decision::Exclude);
+///
+/// assert_eq!(
+///     automaton.decide(&["name", "first"]),
+///     Decision::Include
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct Automaton {
+    negation: bool,
+    states: Vec<State>,
+    transitions: Vec<Transition>,
+    /// The keys of all transitions, concatenated.
+    keys: String,
+}
+
+#[derive(Clone, Debug)]
+struct State {
+    decision: Decision,
+    /// Offsets into [`Automaton::transitions`].
+    start: u32,
+    end: u32,
+}
+
+#[derive(Clone, Debug)]
+struct Transition {
+    /// Offsets into [`Automaton::keys`].
+    key_start: u32,
+    key_end: u32,
+    target: StateId,
+}
+
+impl Automaton {
+    /// Compile `tree` into an [`Automaton`].
+    ///
+    /// Siblings with the same name are compiled into a single transition, as
+    /// with [`Tree::decide`]. Its target is final if any of them is a leaf, and
+    /// otherwise a state with the transitions of all of their children.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tree is larger than 4 GiB.
+    #[must_use]
+    pub fn new(tree: &Tree<'_>) -> Self {
+        let negation = tree.negation();
+        let selected = if negation { EXCLUDE } else { INCLUDE };
+        let final_state = |decision| State {
+            decision,
+            start: 0,
+            end: 0,
+        };
+        let mut automaton = Self {
+            negation,
+            states: vec![
+                final_state(Decision::Include),
+                final_state(Decision::Exclude),
+            ],
+            transitions: Vec::new(),
+            keys: String::new(),
+        };
+        debug_assert_eq!(automaton.states.len(), START.index());
+        // The same-named partial fields waiting to get their transitions, in
+        // state order. The empty set stands for the top level.
+        let mut pending: Vec<Vec<Field<'_>>> = vec![Vec::new()];
+        let mut next = 0;
+        while let Some(fields) = pending.get(next) {
+            let mut children: Vec<_> = if fields.is_empty() {
+                tree.top().collect()
+            } else {
+                fields.iter().flat_map(Field::children).collect()
+            };
+            next += 1;
+            children.sort_by(|a, b| a.name().cmp(b.name()));
+
+            let start = to_u32(automaton.transitions.len());
+            for group in children.chunk_by(|a, b| a.name() == b.name()) {
+                let key_start = to_u32(automaton.keys.len());
+                automaton.keys.push_str(group[0].name());
+                let key_end = to_u32(automaton.keys.len());
+                let target = if group.iter().all(Field::has_children) {
+                    pending.push(group.to_vec());
+                    StateId(to_u32(pending.len() - 1 + START.index()))
+                } else {
+                    selected
+                };
+                automaton.transitions.push(Transition {
+                    key_start,
+                    key_end,
+                    target,
+                });
+            }
+            let end = to_u32(automaton.transitions.len());
+            automaton.states.push(State {
+                decision: Decision::Partial,
+                start,
+                end,
+            });
+        }
+        automaton
+    }
+
+    /// Whether the compiled tree is a denylist rather than an allowlist.
+    #[must_use]
+    pub fn negation(&self) -> bool {
+        self.negation
+    }
+
+    /// The state at the top level of a document.
+    #[must_use]
+    pub fn start(&self) -> StateId {
+        START
+    }
+
+    /// The state reached by descending into `key` from `state`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `state` is out of range for this automaton, which can only
+    /// happen if it was obtained from another one. A state from another
+    /// automaton which is in range gives an unspecified result.
+    #[must_use]
+    pub fn step(&self, state: StateId, key: &str) -> StateId {
+        if state == INCLUDE || state == EXCLUDE {
+            return state;
+        }
+        let state = &self.states[state.index()];
+        let transitions = &self.transitions[state.start as usize..state.end as usize];
+        match transitions.binary_search_by(|transition| self.key(transition).cmp(key)) {
+            Ok(found) => transitions[found].target,
+            Err(_) if self.negation => INCLUDE,
+            Err(_) => EXCLUDE,
+        }
+    }
+
+    /// Whether the values found in `state` should be part of a filtered
+    /// response.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `state` is out of range for this automaton, which can only
+    /// happen if it was obtained from another one. A state from another
+    /// automaton which is in range gives an unspecified result.
+    #[must_use]
+    pub fn decision(&self, state: StateId) -> Decision {
+        self.states[state.index()].decision
+    }
+
+    /// See [`Tree::decide`].
+    #[must_use]
+    pub fn decide(&self, path: impl IntoIterator<Item = impl AsRef<str>>) -> Decision {
+        let state = path
+            .into_iter()
+            .fold(self.start(), |state, key| self.step(state, key.as_ref()));
+        self.decision(state)
+    }
+
+    fn key(&self, transition: &Transition) -> &str {
+        &self.keys[transition.key_start as usize..transition.key_end as usize]
+    }
+}
+
+impl StateId {
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_cases::PATHS;
+    use crate::test_cases::VALID_FILTERS;
+
+    #[test]
+    fn test_matches_decide() {
+        for &filter in VALID_FILTERS {
+            let tree = Tree::parse(filter).unwrap();
+            let automaton = tree.compile();
+            for &path in PATHS {
+                assert_eq!(
+                    automaton.decide(path),
+                    tree.decide(path),
+                    "{filter} {path:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_repeated_siblings() {
+        let automaton = Tree::parse("(a(b),a(c))").unwrap().compile();
+        assert_eq!(automaton.decide(["a", "b"]), Decision::Include);
+        assert_eq!(automaton.decide(["a", "c"]), Decision::Include);
+        assert_eq!(automaton.decide(["a", "d"]), Decision::Exclude);
+        assert_eq!(automaton.states.len(), START.index() + 2);
+    }
+
+    #[test]
+    fn test_wide_tree() {
+        let names: Vec<_> = (0..500).map(|i| format!("field_{i}")).collect();
+        let tree = Tree::parse(format!("({})", names.join(","))).unwrap();
+        let automaton = tree.compile();
+        for name in &names {
+            assert_eq!(automaton.decide([name]), Decision::Include);
+        }
+        assert_eq!(automaton.decide(["field_500"]), Decision::Exclude);
+    }
+}
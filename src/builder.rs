@@ -0,0 +1,220 @@
+//! This module contains the [`TreeBuilder`], which constructs a [`Tree`] from
+//! field names rather than from a filter string.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::parser;
+use crate::tree::InvalidName;
+use crate::tree::NoFields;
+use crate::tree::Tree;
+
+/// Builds a [`Tree`] field by field.
+///
+/// # Example
+///
+/// ```
+/// use z157::TreeBuilder;
+///
+/// let tree = TreeBuilder::new()
+///     .field("name")
+///     .nest("address", |b| b.field("city").field("zip"))
+///     .negate(true)
+///     .build()
+///     .unwrap();
+/// assert!(tree.negation());
+/// assert!(tree.index(&["address", "city"]).is_some());
+/// assert_eq!(tree.free(), "!(name,address(city,zip))");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TreeBuilder {
+    fields: Vec<Node>,
+    negation: bool,
+}
+
+/// A field name along with its children.
+#[derive(Clone, Debug)]
+pub(crate) struct Node {
+    pub name: String,
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    pub fn leaf(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            children: Vec::new(),
+        }
+    }
+}
+
+impl TreeBuilder {
+    /// Create a [`TreeBuilder`] without any fields.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a field which does not have any children.
+    #[must_use]
+    pub fn field(mut self, name: impl Into<String>) -> Self {
+        self.fields.push(Node::leaf(name));
+        self
+    }
+
+    /// Add a field along with the children added by `build`.
+    ///
+    /// The [negation](TreeBuilder::negate) of the builder passed to `build` is
+    /// ignored. If `build` does not add any children, the field is added as
+    /// if by [`field`](TreeBuilder::field).
+    #[must_use]
+    pub fn nest(
+        mut self,
+        name: impl Into<String>,
+        build: impl FnOnce(TreeBuilder) -> TreeBuilder,
+    ) -> Self {
+        self.fields.push(Node {
+            name: name.into(),
+            children: build(TreeBuilder::new()).fields,
+        });
+        self
+    }
+
+    /// Set whether the tree should be a denylist rather than an allowlist.
+    #[must_use]
+    pub fn negate(mut self, negation: bool) -> Self {
+        self.negation = negation;
+        self
+    }
+
+    /// Build a [`Tree`] which owns its buffer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any field name is empty or contains characters
+    /// other than ASCII letters, digits, `-` and `_`, or if no fields were
+    /// added.
+    pub fn build(self) -> Result<Tree<'static>, BuildError> {
+        validate(&self.fields)?;
+        Ok(write(&self.fields, self.negation)?)
+    }
+}
+
+/// Returned when a [`Tree`] cannot be built from field names.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BuildError {
+    /// A field name is not valid.
+    InvalidName(InvalidName),
+    /// There are no fields.
+    NoFields(NoFields),
+}
+
+impl From<InvalidName> for BuildError {
+    fn from(error: InvalidName) -> Self {
+        Self::InvalidName(error)
+    }
+}
+
+impl From<NoFields> for BuildError {
+    fn from(error: NoFields) -> Self {
+        Self::NoFields(error)
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidName(error) => error.fmt(f),
+            Self::NoFields(error) => error.fmt(f),
+        }
+    }
+}
+
+impl core::error::Error for BuildError {}
+
+/// Ensure that all names in `nodes` are valid field names.
+pub(crate) fn validate(nodes: &[Node]) -> Result<(), InvalidName> {
+    for node in nodes {
+        if !parser::FieldName::is_valid(&node.name) {
+            return Err(InvalidName {
+                name: node.name.clone(),
+            });
+        }
+        validate(&node.children)?;
+    }
+    Ok(())
+}
+
+/// Write `nodes` as a filter string and create a [`Tree`] from it.
+///
+/// All names must be valid.
+pub(crate) fn write(nodes: &[Node], negation: bool) -> Result<Tree<'static>, NoFields> {
+    fn write_struct(nodes: &[Node], buffer: &mut String) {
+        buffer.push('(');
+        for (i, node) in nodes.iter().enumerate() {
+            if i > 0 {
+                buffer.push(',');
+            }
+            buffer.push_str(&node.name);
+            if !node.children.is_empty() {
+                write_struct(&node.children, buffer);
+            }
+        }
+        buffer.push(')');
+    }
+
+    if nodes.is_empty() {
+        return Err(NoFields);
+    }
+    let mut buffer = String::new();
+    if negation {
+        buffer.push('!');
+    }
+    write_struct(nodes, &mut buffer);
+    Ok(Tree::from_written(buffer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        let tree = TreeBuilder::new()
+            .nest("a", |b| b.field("b").nest("c", |b| b.field("d")))
+            .field("e")
+            .build()
+            .unwrap();
+        assert!(!tree.negation());
+        let paths: Vec<_> = tree.walk().map(|f| f.path().join(".")).collect();
+        assert_eq!(paths, ["a", "a.b", "a.c", "a.c.d", "e"]);
+    }
+
+    #[test]
+    fn test_nest_without_children_is_leaf() {
+        let tree = TreeBuilder::new().nest("a", |b| b).build().unwrap();
+        assert!(!tree.index(["a"]).unwrap().has_children());
+    }
+
+    #[test]
+    fn test_empty() {
+        let Err(error) = TreeBuilder::new().negate(true).build() else {
+            panic!("a tree needs at least one field");
+        };
+        assert_eq!(error, BuildError::NoFields(NoFields));
+    }
+
+    #[test]
+    fn test_invalid_name() {
+        for name in ["", "a,b", "a(b)", "!a", "a.b"] {
+            let Err(error) = TreeBuilder::new().nest("a", |b| b.field(name)).build() else {
+                panic!("{name:?} should be invalid");
+            };
+            assert_eq!(
+                error,
+                BuildError::InvalidName(InvalidName { name: name.into() })
+            );
+        }
+    }
+}
@@ -0,0 +1,541 @@
+//! This module contains methods for changing the fields of a parsed [`Tree`].
+//!
+//! Every change rewrites the buffer of the tree, which becomes owned, so that
+//! the result is the same as parsing the changed filter string.
+
+use alloc::borrow::ToOwned;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use crate::builder;
+use crate::builder::BuildError;
+use crate::builder::Node;
+use crate::parser;
+use crate::tree::Field;
+use crate::tree::InvalidName;
+use crate::tree::NoFields;
+use crate::tree::Tree;
+
+impl Tree<'_> {
+    /// Add the field at `path`, along with any missing parents.
+    ///
+    /// The field selects its entire subtree, just like a leaf in a parsed
+    /// filter. If it already has children, they are removed. If one of its
+    /// parents is already a leaf, the tree is left as is, since the field is
+    /// already part of that leaf's subtree. An empty `path` has no effect.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any element of `path` is not a valid field name.
+    /// The tree is not changed in that case.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut tree =
+    ///     z157::Tree::parse("(name,address(city))").unwrap();
+    /// tree.insert_path(&["address", "zip"]).unwrap();
+    /// tree.insert_path(&["id"]).unwrap();
+    /// assert_eq!(tree.free(), "(name,address(city,zip),id)");
+    /// ```
+    pub fn insert_path(
+        &mut self,
+        path: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<(), InvalidName> {
+        let path: Vec<_> = path.into_iter().collect();
+        if let Some(invalid) = path
+            .iter()
+            .map(AsRef::as_ref)
+            .find(|name| !parser::FieldName::is_valid(name))
+        {
+            return Err(InvalidName {
+                name: invalid.to_string(),
+            });
+        }
+        if path.is_empty() {
+            return Ok(());
+        }
+        let mut nodes = self.nodes();
+        insert(&mut nodes, &path)?;
+        self.replace(&nodes, self.negation());
+        Ok(())
+    }
+
+    /// Add the fields at `paths` as if by [`insert_path`](Self::insert_path)
+    /// for each path.
+    ///
+    /// This is the fallible version of [`Extend`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a path contains an invalid field name. The tree is
+    /// not changed in that case.
+    pub fn try_extend<P>(&mut self, paths: impl IntoIterator<Item = P>) -> Result<(), InvalidName>
+    where
+        P: IntoIterator,
+        P::Item: AsRef<str>,
+    {
+        let mut nodes = self.nodes();
+        for path in paths {
+            insert(&mut nodes, path)?;
+        }
+        self.replace(&nodes, self.negation());
+        Ok(())
+    }
+
+    /// Remove the field at `path` along with its children.
+    ///
+    /// Parents which are left without children are removed as well, since
+    /// they would otherwise select their entire subtree. Returns `false` if
+    /// there is no field at `path`, in which case the tree is not changed.
+    ///
+    /// As with [`decide`](Tree::decide), siblings with the same name are
+    /// treated as one field, so all of them are removed. If one of them is a
+    /// leaf, `path` lies within its subtree rather than at a field.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree would be left without any fields. The
+    /// tree is not changed in that case. Note that a denylist without fields
+    /// would select everything.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut tree =
+    ///     z157::Tree::parse("(name,address(city))").unwrap();
+    /// assert!(
+    ///     tree.remove_path(&["address", "city"]).unwrap()
+    /// );
+    /// assert!(!tree.remove_path(&["age"]).unwrap());
+    /// assert_eq!(tree.to_string(), "(name)");
+    /// assert!(tree.remove_path(&["name"]).is_err());
+    /// assert_eq!(tree.free(), "(name)");
+    /// ```
+    pub fn remove_path(
+        &mut self,
+        path: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<bool, NoFields> {
+        /// Remove the nodes at `path` from `siblings`, returning whether any
+        /// were found.
+        fn remove(siblings: &mut Vec<Node>, path: &[impl AsRef<str>]) -> bool {
+            let Some((first, rest)) = path.split_first() else {
+                return false;
+            };
+            let first = first.as_ref();
+            if !rest.is_empty()
+                && siblings
+                    .iter()
+                    .any(|node| node.name == first && node.children.is_empty())
+            {
+                return false;
+            }
+            let mut found = false;
+            siblings.retain_mut(|node| {
+                if node.name != first {
+                    return true;
+                }
+                if rest.is_empty() {
+                    found = true;
+                    return false;
+                }
+                if !remove(&mut node.children, rest) {
+                    return true;
+                }
+                found = true;
+                !node.children.is_empty()
+            });
+            found
+        }
+
+        let path: Vec<_> = path.into_iter().collect();
+        let mut nodes = self.nodes();
+        if !remove(&mut nodes, &path) {
+            return Ok(false);
+        }
+        *self = builder::write(&nodes, self.negation())?;
+        Ok(true)
+    }
+
+    /// Change the name of the field at `path` to `name`.
+    ///
+    /// As with [`decide`](Tree::decide), siblings with the same name are
+    /// treated as one field, so all of them are renamed. If one of them is a
+    /// leaf, `path` lies within its subtree rather than at a field.
+    ///
+    /// Returns `false` if there is no field at `path`, in which case the tree
+    /// is not changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `name` is not a valid field name. The tree is not
+    /// changed in that case.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut tree =
+    ///     z157::Tree::parse("(name,address(city))").unwrap();
+    /// assert!(
+    ///     tree.rename_path(&["address", "city"], "town")
+    ///         .unwrap()
+    /// );
+    /// assert_eq!(tree.free(), "(name,address(town))");
+    /// ```
+    pub fn rename_path(
+        &mut self,
+        path: impl IntoIterator<Item = impl AsRef<str>>,
+        name: &str,
+    ) -> Result<bool, InvalidName> {
+        /// Rename the nodes at `path` in `siblings`, returning whether any
+        /// were found.
+        fn rename(siblings: &mut [Node], path: &[impl AsRef<str>], name: &str) -> bool {
+            let Some((first, rest)) = path.split_first() else {
+                return false;
+            };
+            let first = first.as_ref();
+            if !rest.is_empty()
+                && siblings
+                    .iter()
+                    .any(|node| node.name == first && node.children.is_empty())
+            {
+                return false;
+            }
+            let mut found = false;
+            for node in siblings.iter_mut().filter(|node| node.name == first) {
+                if rest.is_empty() {
+                    name.clone_into(&mut node.name);
+                    found = true;
+                } else if rename(&mut node.children, rest, name) {
+                    found = true;
+                }
+            }
+            found
+        }
+
+        if !parser::FieldName::is_valid(name) {
+            return Err(InvalidName {
+                name: name.to_string(),
+            });
+        }
+        let path: Vec<_> = path.into_iter().collect();
+        let mut nodes = self.nodes();
+        if !rename(&mut nodes, &path, name) {
+            return Ok(false);
+        }
+        self.replace(&nodes, self.negation());
+        Ok(true)
+    }
+
+    /// Set whether these fields should represent a denylist rather than an
+    /// allowlist.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut tree = z157::Tree::parse("(name)").unwrap();
+    /// tree.set_negation(true);
+    /// assert_eq!(tree.free(), "!(name)");
+    /// ```
+    pub fn set_negation(&mut self, negation: bool) {
+        if negation != self.negation() {
+            let nodes = self.nodes();
+            self.replace(&nodes, negation);
+        }
+    }
+
+    /// Copy the fields of this tree into owned [`Node`]s.
+    fn nodes(&self) -> Vec<Node> {
+        fn node(field: &Field<'_>) -> Node {
+            Node {
+                name: field.name().to_string(),
+                children: field.children().map(|child| node(&child)).collect(),
+            }
+        }
+        self.top().map(|field| node(&field)).collect()
+    }
+
+    /// Replace this tree with one written from `nodes`, which must have valid
+    /// names and must not be empty.
+    fn replace(&mut self, nodes: &[Node], negation: bool) {
+        *self = builder::write(nodes, negation).expect("the tree has fields");
+    }
+}
+
+impl Tree<'static> {
+    /// Build a [`Tree`] without negation from paths, as if by
+    /// [`insert_path`](Tree::insert_path) for each path.
+    ///
+    /// This is the fallible version of [`FromIterator`], for paths which are
+    /// not known to be valid, e.g., when they are read from a configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a path contains an invalid field name, or if there
+    /// are no paths.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use z157::BuildError;
+    /// use z157::Tree;
+    ///
+    /// let columns = ["name", "address.city"];
+    /// let tree = Tree::try_from_paths(
+    ///     columns.iter().map(|column| column.split('.')),
+    /// )
+    /// .unwrap();
+    /// assert_eq!(tree.to_string(), "(name,address(city))");
+    ///
+    /// let error =
+    ///     Tree::try_from_paths([["address", "zip code"]])
+    ///         .err();
+    /// assert!(matches!(
+    ///     error,
+    ///     Some(BuildError::InvalidName(_))
+    /// ));
+    /// ```
+    pub fn try_from_paths<P>(paths: impl IntoIterator<Item = P>) -> Result<Self, BuildError>
+    where
+        P: IntoIterator,
+        P::Item: AsRef<str>,
+    {
+        let mut nodes = Vec::new();
+        for path in paths {
+            insert(&mut nodes, path)?;
+        }
+        Ok(builder::write(&nodes, false)?)
+    }
+}
+
+/// Builds a [`Tree`] without negation from paths, as if by
+/// [`insert_path`](Tree::insert_path) for each path.
+///
+/// A path is anything which can be iterated over to yield field names, such as
+/// `&[&str]` or `Vec<String>`.
+///
+/// # Panics
+///
+/// Panics if a path contains an invalid field name, or if there are no paths,
+/// since a tree needs at least one field. Use
+/// [`try_from_paths`](Tree::try_from_paths) for paths which may be invalid.
+///
+/// # Example
+///
+/// ```
+/// use z157::Tree;
+///
+/// let tree: Tree = [
+///     vec!["name"],
+///     vec!["address", "city"],
+///     vec!["address", "zip"],
+/// ]
+/// .into_iter()
+/// .collect();
+/// assert_eq!(
+///     tree.to_string(),
+///     "(name,address(city,zip))"
+/// );
+/// ```
+impl<P> FromIterator<P> for Tree<'static>
+where
+    P: IntoIterator,
+    P::Item: AsRef<str>,
+{
+    fn from_iter<T: IntoIterator<Item = P>>(paths: T) -> Self {
+        Tree::try_from_paths(paths).unwrap_or_else(|error| panic!("{error}"))
+    }
+}
+
+/// Adds paths as if by [`insert_path`](Tree::insert_path) for each path.
+///
+/// # Panics
+///
+/// Panics if a path contains an invalid field name. The tree is not changed
+/// in that case. Use [`try_extend`](Tree::try_extend) for paths which may be
+/// invalid.
+impl<P> Extend<P> for Tree<'_>
+where
+    P: IntoIterator,
+    P::Item: AsRef<str>,
+{
+    fn extend<T: IntoIterator<Item = P>>(&mut self, paths: T) {
+        self.try_extend(paths)
+            .unwrap_or_else(|error| panic!("{error}"));
+    }
+}
+
+/// Add the field at `path` to `siblings`. See [`Tree::insert_path`].
+///
+/// Returns an error if `path` contains an invalid field name, in which case
+/// `siblings` may have been changed.
+fn insert<S: AsRef<str>>(
+    mut siblings: &mut Vec<Node>,
+    path: impl IntoIterator<Item = S>,
+) -> Result<(), InvalidName> {
+    let mut path = path.into_iter().peekable();
+    while let Some(name) = path.next() {
+        let name = name.as_ref();
+        if !parser::FieldName::is_valid(name) {
+            return Err(InvalidName {
+                name: name.to_string(),
+            });
+        }
+        let last = path.peek().is_none();
+        match siblings.iter().position(|node| node.name == name) {
+            Some(position) if last => siblings[position].children.clear(),
+            // The path is already part of the subtree selected by this leaf.
+            Some(position) if siblings[position].children.is_empty() => return Ok(()),
+            Some(position) => siblings = &mut siblings[position].children,
+            None => {
+                siblings.push(Node::leaf(name));
+                let position = siblings.len() - 1;
+                siblings = &mut siblings[position].children;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BuildError;
+    use crate::InvalidName;
+    use crate::NoFields;
+    use crate::Tree;
+
+    #[test]
+    fn test_insert_path() {
+        let mut tree = Tree::parse("(a(b),c)").unwrap();
+        tree.insert_path(["a", "d", "e"]).unwrap();
+        assert_eq!(tree.to_string(), "(a(b,d(e)),c)");
+        // Already part of the subtree selected by `c`.
+        tree.insert_path(["c", "f"]).unwrap();
+        assert_eq!(tree.to_string(), "(a(b,d(e)),c)");
+        // Selects all of `a`.
+        tree.insert_path(["a"]).unwrap();
+        assert_eq!(tree.to_string(), "(a,c)");
+        tree.insert_path([""; 0]).unwrap();
+        assert_eq!(tree.to_string(), "(a,c)");
+        assert_eq!(tree.insert_path(["a", "b,c"]).unwrap_err().name, "b,c");
+        assert_eq!(tree.free(), "(a,c)");
+    }
+
+    #[test]
+    fn test_remove_path() {
+        let mut tree = Tree::parse("!(a(b(c),d),e)").unwrap();
+        assert!(!tree.remove_path([""; 0]).unwrap());
+        assert!(!tree.remove_path(["a", "x"]).unwrap());
+        assert!(!tree.remove_path(["e", "x"]).unwrap());
+        assert!(tree.remove_path(["a", "b", "c"]).unwrap());
+        assert_eq!(tree.to_string(), "!(a(d),e)");
+        assert!(tree.remove_path(["a", "d"]).unwrap());
+        assert_eq!(tree.remove_path(["e"]), Err(NoFields));
+        assert_eq!(tree.to_string(), "!(e)");
+    }
+
+    #[test]
+    fn test_remove_repeated_siblings() {
+        let mut tree = Tree::parse("!(secret(x),secret(y),z)").unwrap();
+        assert!(tree.remove_path(["secret"]).unwrap());
+        assert_eq!(tree.to_string(), "!(z)");
+
+        let mut tree = Tree::parse("(a(b),a(c),d)").unwrap();
+        assert!(tree.remove_path(["a", "c"]).unwrap());
+        assert_eq!(tree.to_string(), "(a(b),d)");
+
+        let mut tree = Tree::parse("(a(b,c),a(b(x)),d)").unwrap();
+        assert!(tree.remove_path(["a", "b"]).unwrap());
+        assert_eq!(tree.to_string(), "(a(c),d)");
+
+        // `a.b` lies within the leaf `a`.
+        let mut tree = Tree::parse("(a(b),a,d)").unwrap();
+        assert!(!tree.remove_path(["a", "b"]).unwrap());
+        assert_eq!(tree.to_string(), "(a(b),a,d)");
+    }
+
+    #[test]
+    fn test_rename_path() {
+        let mut tree = Tree::parse("(a(b),c)").unwrap();
+        assert!(tree.rename_path(["a"], "x").unwrap());
+        assert!(!tree.rename_path(["a"], "y").unwrap());
+        assert!(!tree.rename_path([""; 0], "y").unwrap());
+        assert!(tree.rename_path(["x", "b"], "y").unwrap());
+        assert_eq!(tree.rename_path(["c"], "").unwrap_err().name, "");
+        assert_eq!(tree.index(["x", "y"]).unwrap().path(), ["x", "y"]);
+        assert_eq!(tree.free(), "(x(y),c)");
+
+        let mut tree = Tree::parse("(a(b),a,a(c(b)))").unwrap();
+        assert!(tree.rename_path(["a"], "z").unwrap());
+        assert_eq!(tree.to_string(), "(z(b),z,z(c(b)))");
+        // `z.b` lies within the leaf `z`.
+        assert!(!tree.rename_path(["z", "b"], "y").unwrap());
+
+        let mut tree = Tree::parse("(a(b),a(c(b),b(d)))").unwrap();
+        assert!(tree.rename_path(["a", "b"], "y").unwrap());
+        assert_eq!(tree.to_string(), "(a(y),a(c(b),y(d)))");
+    }
+
+    #[test]
+    fn test_set_negation() {
+        let mut tree = Tree::parse("!(a(b))").unwrap();
+        tree.set_negation(false);
+        assert!(!tree.negation());
+        assert_eq!(tree.free(), "(a(b))");
+    }
+
+    #[test]
+    fn test_from_iter() {
+        let paths: Vec<Vec<String>> = ["a.b", "a.c.d", "e", "a.c", "e.f"]
+            .iter()
+            .map(|path| path.split('.').map(String::from).collect())
+            .collect();
+        let tree: Tree = paths.into_iter().collect();
+        assert!(!tree.negation());
+        assert_eq!(tree.to_string(), "(a(b,c),e)");
+    }
+
+    #[test]
+    fn test_extend() {
+        let mut tree = Tree::parse("!(a(b))").unwrap();
+        tree.extend([["a", "c"], ["d", "e"]]);
+        assert!(tree.negation());
+        assert_eq!(tree.to_string(), "!(a(b,c),d(e))");
+    }
+
+    #[test]
+    fn test_try_from_paths() {
+        let tree = Tree::try_from_paths(["a.b", "c", "a.d"].map(|path| path.split('.'))).unwrap();
+        assert_eq!(tree.to_string(), "(a(b,d),c)");
+        let Err(error) = Tree::try_from_paths([["a", "b"], ["c", ""]]) else {
+            panic!("the empty name is invalid");
+        };
+        assert_eq!(
+            error,
+            BuildError::InvalidName(InvalidName {
+                name: String::new()
+            })
+        );
+        let Err(error) = Tree::try_from_paths(std::iter::empty::<&[&str]>()) else {
+            panic!("a tree needs at least one field");
+        };
+        assert_eq!(error, BuildError::NoFields(NoFields));
+
+        let mut tree = Tree::parse("(a)").unwrap();
+        let error = tree.try_extend([["b"], ["c("]]).unwrap_err();
+        assert_eq!(error.name, "c(");
+        assert_eq!(tree.to_string(), "(a)");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid field name")]
+    fn test_from_iter_invalid_name() {
+        let _: Tree = [["a", "b.c"]].into_iter().collect();
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one field")]
+    fn test_from_iter_empty() {
+        let _: Tree = std::iter::empty::<&[&str]>().collect();
+    }
+}
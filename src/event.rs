@@ -0,0 +1,225 @@
+//! This module contains [`events`], which validates a filter string while
+//! reporting its structure, without building a [`Tree`](crate::Tree).
+
+use alloc::format;
+use alloc::string::ToString;
+
+use crate::parser;
+use crate::token::Token;
+use crate::token::TokenKind;
+use crate::token::Tokens;
+use crate::token::tokenize;
+
+/// A part of a filter string reported by [`events`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Event<'s> {
+    /// The fields are a denylist. Only ever the first event.
+    Negation,
+    /// A struct is opened, either at the top level or after the [`Name`]
+    /// of its parent.
+    ///
+    /// [`Name`]: Event::Name
+    StartStruct,
+    /// A field name.
+    Name(&'s str),
+    /// The most recently started struct is closed.
+    EndStruct,
+}
+
+/// Parse `s` one [`Event`] at a time.
+///
+/// The input is validated while iterating, and an error is yielded as soon as
+/// `s` is found not to match the expected format, after which the iterator
+/// ends. The events before an error are reported even though `s` is invalid.
+/// Neither a syntax tree nor a [`Tree`](crate::Tree) is built, so nothing is
+/// allocated unless there is an error.
+///
+/// # Example
+///
+/// ```
+/// use z157::Event;
+///
+/// /// The number of levels of nested structs in a valid filter.
+/// fn depth(s: &str) -> Result<usize, z157::parser::Error> {
+///     let mut depth = 0;
+///     let mut max = 0;
+///     for event in z157::events(s) {
+///         match event? {
+///             Event::StartStruct => {
+///                 depth += 1;
+///                 max = max.max(depth);
+///             }
+///             Event::EndStruct => depth -= 1,
+///             _ => {}
+///         }
+///     }
+///     Ok(max)
+/// }
+///
+/// assert_eq!(depth("!(a(b(c)),d)").unwrap(), 3);
+/// assert!(depth("(a(b(c)),)").is_err());
+/// ```
+#[must_use]
+pub fn events(s: &str) -> Events<'_> {
+    Events {
+        tokens: tokenize(s),
+        state: State::Start,
+        depth: 0,
+    }
+}
+
+/// An iterator over the [`Event`]s of a filter string. See [`events`].
+#[derive(Clone, Debug)]
+pub struct Events<'s> {
+    tokens: Tokens<'s>,
+    state: State,
+    /// The number of structs which have been started but not ended.
+    depth: usize,
+}
+
+/// What [`Events`] expects next.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    /// `!` or `(`.
+    Start,
+    /// `(` after `!`.
+    Negated,
+    /// A field name after `(` or `,`.
+    Name,
+    /// `(`, `,` or `)` after a field name.
+    AfterName,
+    /// `,` or `)` after a nested struct.
+    AfterStruct,
+    /// Nothing, since the top-level struct has ended.
+    End,
+    /// Nothing, since an error has been reported.
+    Failed,
+}
+
+impl State {
+    /// A description of the tokens which are expected in this state.
+    fn expected(self) -> &'static str {
+        match self {
+            State::Start => "'!' or '('",
+            State::Negated => "'('",
+            State::Name => "field name",
+            State::AfterName => "'(', ',' or ')'",
+            State::AfterStruct => "',' or ')'",
+            State::End | State::Failed => "end of input",
+        }
+    }
+}
+
+impl<'s> Events<'s> {
+    fn fail(&mut self, token: Option<Token<'_>>, offset: usize) -> parser::Error {
+        let expected = self.state.expected();
+        self.state = State::Failed;
+        let found = match token {
+            Some(token) => format!("{:?}", token.text),
+            None => "end of input".to_string(),
+        };
+        parser::Error::new(
+            format!("expected {expected}, found {found} at offset {offset}"),
+            offset,
+        )
+    }
+
+    fn end_struct(&mut self) -> Event<'s> {
+        self.depth -= 1;
+        self.state = if self.depth == 0 {
+            State::End
+        } else {
+            State::AfterStruct
+        };
+        Event::EndStruct
+    }
+}
+
+impl<'s> Iterator for Events<'s> {
+    type Item = Result<Event<'s>, parser::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.state == State::Failed {
+            return None;
+        }
+        let Some(token) = self.tokens.next() else {
+            if self.state == State::End {
+                return None;
+            }
+            let offset = self.tokens.offset();
+            return Some(Err(self.fail(None, offset)));
+        };
+        let event = match (self.state, token.kind) {
+            (State::Start, TokenKind::Bang) => {
+                self.state = State::Negated;
+                Event::Negation
+            }
+            (State::Start | State::Negated | State::AfterName, TokenKind::LParen) => {
+                self.depth += 1;
+                self.state = State::Name;
+                Event::StartStruct
+            }
+            (State::Name, TokenKind::Name) => {
+                self.state = State::AfterName;
+                Event::Name(token.text)
+            }
+            (State::AfterName | State::AfterStruct, TokenKind::Comma) => {
+                self.state = State::Name;
+                return self.next();
+            }
+            (State::AfterName | State::AfterStruct, TokenKind::RParen) => self.end_struct(),
+            _ => {
+                let offset = token.span.start;
+                return Some(Err(self.fail(Some(token), offset)));
+            }
+        };
+        Some(Ok(event))
+    }
+}
+
+impl core::iter::FusedIterator for Events<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_cases::INVALID_FILTERS;
+    use crate::test_cases::VALID_FILTERS;
+
+    #[test]
+    fn test_events() {
+        let events: Vec<_> = events("!(a(b),c)").map(Result::unwrap).collect();
+        assert_eq!(
+            events,
+            [
+                Event::Negation,
+                Event::StartStruct,
+                Event::Name("a"),
+                Event::StartStruct,
+                Event::Name("b"),
+                Event::EndStruct,
+                Event::Name("c"),
+                Event::EndStruct,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filters() {
+        for &s in VALID_FILTERS {
+            assert!(events(s).all(|event| event.is_ok()), "{s:?}");
+        }
+        for &s in INVALID_FILTERS {
+            assert!(events(s).any(|event| event.is_err()), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn test_fused_after_error() {
+        let mut events = events("(a,,b)");
+        assert_eq!(events.next().unwrap().unwrap(), Event::StartStruct);
+        assert_eq!(events.next().unwrap().unwrap(), Event::Name("a"));
+        assert_eq!(events.next().unwrap().unwrap_err().offset(), 3);
+        assert!(events.next().is_none());
+    }
+}
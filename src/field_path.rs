@@ -0,0 +1,346 @@
+//! This module contains [`FieldPath`], an owned path to a field.
+
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use crate::parser::FieldName;
+use crate::tree::Field;
+
+/// An owned path to a field, such as `address.city`.
+///
+/// A `FieldPath` can be used wherever a path is expected, e.g., in
+/// [`Tree::index`](crate::Tree::index) and
+/// [`Tree::decide`](crate::Tree::decide). It is written with dots between the
+/// field names, and can be converted to and from a [JSON
+/// Pointer](https://www.rfc-editor.org/rfc/rfc6901).
+///
+/// Every field name in a `FieldPath` is a valid field name, i.e., it is
+/// non-empty and consists of ASCII letters, digits, `-` and `_`. In
+/// particular, no field name contains a `.`, so [`Display`](fmt::Display) and
+/// [`FromStr`] round-trip.
+///
+/// # Example
+///
+/// ```
+/// use z157::FieldPath;
+/// use z157::Tree;
+///
+/// let tree = Tree::parse("(address(city))").unwrap();
+/// let path: FieldPath = "address.city".parse().unwrap();
+/// let city = tree.index(&path).unwrap();
+/// assert_eq!(FieldPath::from(&city), path);
+/// assert_eq!(path.to_string(), "address.city");
+/// assert_eq!(path.to_json_pointer(), "/address/city");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FieldPath {
+    segments: Vec<String>,
+}
+
+impl FieldPath {
+    /// Create an empty [`FieldPath`], which refers to the whole document.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a [JSON Pointer](https://www.rfc-editor.org/rfc/rfc6901) such as
+    /// `/address/city`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `pointer` is neither empty nor starts with `/`, if
+    /// it contains a `~` which is not part of an escape sequence, or if any
+    /// of its reference tokens is not a valid field name.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use z157::FieldPath;
+    /// use z157::InvalidPath;
+    ///
+    /// let path =
+    ///     FieldPath::from_json_pointer("/address/city")
+    ///         .unwrap();
+    /// assert_eq!(
+    ///     path.iter().collect::<Vec<_>>(),
+    ///     ["address", "city"]
+    /// );
+    /// assert_eq!(
+    ///     FieldPath::from_json_pointer("/a~1b"),
+    ///     Err(InvalidPath::InvalidName)
+    /// );
+    /// ```
+    pub fn from_json_pointer(pointer: &str) -> Result<Self, InvalidPath> {
+        if pointer.is_empty() {
+            return Ok(Self::new());
+        }
+        let Some(pointer) = pointer.strip_prefix('/') else {
+            return Err(InvalidPath::MissingSlash);
+        };
+        pointer
+            .split('/')
+            .map(|segment| {
+                let mut unescaped = String::with_capacity(segment.len());
+                let mut chars = segment.chars();
+                while let Some(c) = chars.next() {
+                    unescaped.push(match c {
+                        '~' => match chars.next() {
+                            Some('0') => '~',
+                            Some('1') => '/',
+                            _ => return Err(InvalidPath::InvalidEscape),
+                        },
+                        c => c,
+                    });
+                }
+                checked(unescaped)
+            })
+            .collect()
+    }
+
+    /// Write this path as a [JSON Pointer](https://www.rfc-editor.org/rfc/rfc6901).
+    ///
+    /// Field names never contain `~` or `/`, so nothing needs to be escaped.
+    #[must_use]
+    pub fn to_json_pointer(&self) -> String {
+        let mut pointer = String::new();
+        for segment in &self.segments {
+            pointer.push('/');
+            pointer.push_str(segment);
+        }
+        pointer
+    }
+
+    /// Iterate over the field names in this path, from the top level down.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &str> + ExactSizeIterator {
+        self.segments.iter().map(String::as_str)
+    }
+
+    /// The number of field names in this path.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Whether this path refers to the whole document.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Whether `prefix` is this path or one of its ancestors.
+    #[must_use]
+    pub fn starts_with(&self, prefix: &FieldPath) -> bool {
+        self.segments.starts_with(&prefix.segments)
+    }
+
+    /// The path to the parent of the field, unless this path is empty.
+    #[must_use]
+    pub fn parent(&self) -> Option<FieldPath> {
+        let (_, parent) = self.segments.split_last()?;
+        Some(Self {
+            segments: parent.to_vec(),
+        })
+    }
+
+    /// Append a field name to this path.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a valid field name.
+    pub fn push(&mut self, name: impl Into<String>) {
+        let name = name.into();
+        assert!(FieldName::is_valid(&name), "invalid field name: {name:?}");
+        self.segments.push(name);
+    }
+
+    /// Remove and return the last field name in this path.
+    pub fn pop(&mut self) -> Option<String> {
+        self.segments.pop()
+    }
+}
+
+/// Writes the field names separated by dots, e.g., `address.city`.
+impl fmt::Display for FieldPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            if i > 0 {
+                f.write_str(".")?;
+            }
+            f.write_str(segment)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses field names separated by dots, e.g., `address.city`. The empty
+/// string is parsed as the empty path.
+impl FromStr for FieldPath {
+    type Err = InvalidPath;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Ok(Self::new());
+        }
+        s.split('.')
+            .map(|segment| checked(segment.to_string()))
+            .collect()
+    }
+}
+
+/// Ensure that `segment` is a valid field name.
+fn checked(segment: String) -> Result<String, InvalidPath> {
+    if segment.is_empty() {
+        Err(InvalidPath::EmptySegment)
+    } else if FieldName::is_valid(&segment) {
+        Ok(segment)
+    } else {
+        Err(InvalidPath::InvalidName)
+    }
+}
+
+impl From<&Field<'_>> for FieldPath {
+    fn from(field: &Field<'_>) -> Self {
+        field.path_iter().collect()
+    }
+}
+
+/// # Panics
+///
+/// Panics if any name is not a valid field name.
+impl<S: Into<String>> FromIterator<S> for FieldPath {
+    fn from_iter<T: IntoIterator<Item = S>>(iter: T) -> Self {
+        let mut path = Self::new();
+        for name in iter {
+            path.push(name);
+        }
+        path
+    }
+}
+
+impl<'a> IntoIterator for &'a FieldPath {
+    type Item = &'a str;
+    type IntoIter = core::iter::Map<core::slice::Iter<'a, String>, fn(&String) -> &str>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.segments.iter().map(String::as_str)
+    }
+}
+
+/// Returned when a string cannot be converted into a [`FieldPath`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvalidPath {
+    /// A path contains an empty field name.
+    EmptySegment,
+    /// A path contains a field name with characters other than ASCII
+    /// letters, digits, `-` and `_`.
+    InvalidName,
+    /// A non-empty JSON Pointer does not start with `/`.
+    MissingSlash,
+    /// A JSON Pointer contains a `~` which is not followed by `0` or `1`.
+    InvalidEscape,
+}
+
+impl fmt::Display for InvalidPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidPath::EmptySegment => f.write_str("path contains an empty field name"),
+            InvalidPath::InvalidName => f.write_str("path contains an invalid field name"),
+            InvalidPath::MissingSlash => f.write_str("JSON Pointer does not start with '/'"),
+            InvalidPath::InvalidEscape => f.write_str("JSON Pointer contains an invalid escape"),
+        }
+    }
+}
+
+impl core::error::Error for InvalidPath {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Tree;
+
+    #[test]
+    fn test_dotted() {
+        let path: FieldPath = "a.b.c".parse().unwrap();
+        assert_eq!(path.len(), 3);
+        assert_eq!(path.to_string(), "a.b.c");
+        assert_eq!("".parse::<FieldPath>().unwrap(), FieldPath::new());
+        assert_eq!("a..b".parse::<FieldPath>(), Err(InvalidPath::EmptySegment));
+        assert_eq!("a.".parse::<FieldPath>(), Err(InvalidPath::EmptySegment));
+        for s in ["a(b).c", "a,b", "!a", "a b"] {
+            assert_eq!(s.parse::<FieldPath>(), Err(InvalidPath::InvalidName));
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for s in ["", "a", "a.b-c.d_e", "A.0"] {
+            let path: FieldPath = s.parse().unwrap();
+            assert_eq!(path.to_string(), s);
+            let pointer = path.to_json_pointer();
+            assert_eq!(FieldPath::from_json_pointer(&pointer).unwrap(), path);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid field name")]
+    fn test_push_invalid_name() {
+        FieldPath::new().push("a.b");
+    }
+
+    #[test]
+    fn test_json_pointer() {
+        for pointer in ["", "/a", "/a/b"] {
+            let path = FieldPath::from_json_pointer(pointer).unwrap();
+            assert_eq!(path.to_json_pointer(), pointer);
+        }
+        for pointer in ["/a.b", "/a~0b", "/a~1b"] {
+            assert_eq!(
+                FieldPath::from_json_pointer(pointer),
+                Err(InvalidPath::InvalidName)
+            );
+        }
+        for pointer in ["/", "/a/", "/a//b"] {
+            assert_eq!(
+                FieldPath::from_json_pointer(pointer),
+                Err(InvalidPath::EmptySegment)
+            );
+        }
+        assert_eq!(
+            FieldPath::from_json_pointer("a"),
+            Err(InvalidPath::MissingSlash)
+        );
+        for pointer in ["/~", "/~2", "/a~"] {
+            assert_eq!(
+                FieldPath::from_json_pointer(pointer),
+                Err(InvalidPath::InvalidEscape)
+            );
+        }
+    }
+
+    #[test]
+    fn test_relations() {
+        let path: FieldPath = "a.b".parse().unwrap();
+        let parent = path.parent().unwrap();
+        assert_eq!(parent.to_string(), "a");
+        assert!(path.starts_with(&parent));
+        assert!(path.starts_with(&path));
+        assert!(path.starts_with(&FieldPath::new()));
+        assert!(!parent.starts_with(&path));
+        assert_eq!(parent.parent(), Some(FieldPath::new()));
+        assert_eq!(FieldPath::new().parent(), None);
+        assert!(parent < path);
+    }
+
+    #[test]
+    fn test_accepted_by_tree() {
+        let tree = Tree::parse("!(a(b))").unwrap();
+        let path: FieldPath = "a.b".parse().unwrap();
+        assert_eq!(FieldPath::from(&tree.index(&path).unwrap()), path);
+        assert_eq!(tree.decide(&path), crate::Decision::Exclude);
+        assert_eq!(tree.compile().decide(&path), crate::Decision::Exclude);
+    }
+}
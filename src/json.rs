@@ -0,0 +1,510 @@
+//! Filtering of [`serde_json::Value`]s by a [`Tree`].
+//!
+//! Fields which are selected by the tree but missing from the value are
+//! ignored. Objects keep the order of their remaining keys.
+//!
+//! Arrays are transparent: a field which applies to an array applies to each
+//! of its elements, including elements of nested arrays. For example,
+//! `(items(id))` selects the `id` of every object in the `items` array.
+//!
+//! A value which is neither an object nor an array cannot be filtered by the
+//! children of a field. Such values are kept as they are in
+//! [`Mode::Lenient`], and cause an error in [`Mode::Strict`]. `null` is
+//! always kept, since there is nothing to filter.
+//!
+//! To find out which fields of the tree did not match anything in the value,
+//! e.g., to warn about unknown fields, use [`apply_reporting`] or
+//! [`project_reporting`].
+//!
+//! # Example
+//!
+//! ```
+//! use serde_json::json;
+//! use z157::Tree;
+//!
+//! let mut user = json!({
+//!     "name": "Ford Prefect",
+//!     "bio": {"height_cm": 180, "year_of_birth": 1779},
+//!     "towels": [{"color": "blue", "size": "large"}],
+//!     "last_seen": "1979-12-28",
+//! });
+//! let tree =
+//!     Tree::parse("(name,bio(height_cm),towels(color),age)")
+//!         .unwrap();
+//! z157::json::apply(&tree, &mut user);
+//! assert_eq!(
+//!     user,
+//!     json!({
+//!         "name": "Ford Prefect",
+//!         "bio": {"height_cm": 180},
+//!         "towels": [{"color": "blue"}],
+//!     })
+//! );
+//! ```
+
+use alloc::collections::BTreeSet;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt;
+
+use serde_json::Map;
+use serde_json::Value;
+
+use crate::field_path::FieldPath;
+use crate::matcher::Matcher;
+use crate::tree::Decision;
+use crate::tree::Field;
+use crate::tree::FieldId;
+use crate::tree::Tree;
+
+/// How to treat values which cannot be filtered by the children of a field,
+/// such as the string `"Ford"` for the filter `(name(first))`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// Keep such values as they are.
+    #[default]
+    Lenient,
+    /// Fail with a [`NotAnObject`] error.
+    Strict,
+}
+
+/// Remove everything from `value` which is not selected by `tree`, in
+/// [`Mode::Lenient`].
+pub fn apply(tree: &Tree<'_>, value: &mut Value) {
+    let result = apply_with(tree, value, Mode::Lenient);
+    debug_assert!(result.is_ok(), "lenient filtering does not fail");
+}
+
+/// Copy everything from `value` which is selected by `tree`, in
+/// [`Mode::Lenient`].
+///
+/// This is the same as [`apply`] on a clone of `value`, except that the parts
+/// which are filtered out are never cloned.
+#[must_use]
+pub fn project(tree: &Tree<'_>, value: &Value) -> Value {
+    project_with(tree, value, Mode::Lenient).unwrap_or_else(|_| unreachable!())
+}
+
+/// Remove everything from `value` which is not selected by `tree`.
+///
+/// # Errors
+///
+/// In [`Mode::Strict`], returns an error if a field with children applies to
+/// a value which is neither an object, an array nor `null`. `value` may be
+/// partially filtered in that case.
+///
+/// # Example
+///
+/// ```
+/// use serde_json::json;
+/// use z157::Tree;
+/// use z157::json::Mode;
+///
+/// let tree = Tree::parse("(name(first))").unwrap();
+/// let mut value = json!({"name": "Ford"});
+/// let error =
+///     z157::json::apply_with(&tree, &mut value, Mode::Strict)
+///         .unwrap_err();
+/// assert_eq!(error.path.to_string(), "name");
+/// ```
+pub fn apply_with(tree: &Tree<'_>, value: &mut Value, mode: Mode) -> Result<(), NotAnObject> {
+    apply_inner(&mut tree.matcher(), value, mode, None)
+}
+
+/// Copy everything from `value` which is selected by `tree`.
+///
+/// # Errors
+///
+/// In [`Mode::Strict`], returns an error if a field with children applies to
+/// a value which is neither an object, an array nor `null`.
+pub fn project_with(tree: &Tree<'_>, value: &Value, mode: Mode) -> Result<Value, NotAnObject> {
+    project_inner(&mut tree.matcher(), value, mode, None)
+}
+
+/// Remove everything from `value` which is not selected by `tree`, in
+/// [`Mode::Lenient`], and report the fields of `tree` which did not match
+/// anything in `value`.
+///
+/// # Example
+///
+/// ```
+/// use serde_json::json;
+/// use z157::Tree;
+/// use z157::json::Reason;
+///
+/// let tree = Tree::parse("(name(first),age)").unwrap();
+/// let mut value = json!({"name": "Ford", "bio": {}});
+/// let unmatched = z157::json::apply_reporting(&tree, &mut value);
+/// assert_eq!(value, json!({"name": "Ford"}));
+///
+/// assert_eq!(unmatched[0].field.path(), ["name", "first"]);
+/// assert_eq!(unmatched[0].field.span(), 6..11);
+/// assert_eq!(unmatched[0].reason, Reason::NotAnObject);
+/// assert_eq!(unmatched[1].field.path(), ["age"]);
+/// assert_eq!(unmatched[1].reason, Reason::MissingKey);
+/// ```
+pub fn apply_reporting<'t>(tree: &'t Tree<'_>, value: &mut Value) -> Vec<Unmatched<'t>> {
+    let mut seen = Seen::default();
+    let result = apply_inner(&mut tree.matcher(), value, Mode::Lenient, Some(&mut seen));
+    debug_assert!(result.is_ok(), "lenient filtering does not fail");
+    seen.unmatched(tree)
+}
+
+/// Copy everything from `value` which is selected by `tree`, in
+/// [`Mode::Lenient`], and report the fields of `tree` which did not match
+/// anything in `value`. See [`apply_reporting`].
+#[must_use]
+pub fn project_reporting<'t>(tree: &'t Tree<'_>, value: &Value) -> (Value, Vec<Unmatched<'t>>) {
+    let mut seen = Seen::default();
+    let projected = project_inner(&mut tree.matcher(), value, Mode::Lenient, Some(&mut seen))
+        .unwrap_or_else(|_| unreachable!());
+    (projected, seen.unmatched(tree))
+}
+
+/// A field of a [`Tree`] which did not match anything in a filtered value.
+///
+/// Only the topmost unmatched fields are reported, since their children
+/// cannot match either.
+#[derive(Clone)]
+pub struct Unmatched<'t> {
+    /// The topmost field which did not match.
+    pub field: Field<'t>,
+    /// Why the field did not match.
+    pub reason: Reason,
+}
+
+/// Why a field did not match anything. See [`Unmatched`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Reason {
+    /// None of the objects where the field applies has the field as a key.
+    MissingKey,
+    /// The field applies only to values which are neither objects, arrays
+    /// nor `null`, such as `first` in `(name(first))` for `{"name": "Ford"}`.
+    NotAnObject,
+}
+
+/// The positions in the tree which were found while filtering a value, for
+/// reporting [`Unmatched`] fields. `None` stands for the top level.
+#[derive(Default)]
+struct Seen {
+    /// Fields which matched a key.
+    matched: BTreeSet<FieldId>,
+    /// Positions where an object was found.
+    objects: BTreeSet<Option<FieldId>>,
+    /// Positions where a value other than an object, an array or `null` was
+    /// found.
+    scalars: BTreeSet<Option<FieldId>>,
+}
+
+impl Seen {
+    fn key(&mut self, matcher: &Matcher<'_>) {
+        self.matched.extend(matcher.fields().iter().map(Field::id));
+    }
+
+    fn object(&mut self, matcher: &Matcher<'_>) {
+        self.objects.extend(Self::positions(matcher));
+    }
+
+    fn scalar(&mut self, matcher: &Matcher<'_>) {
+        self.scalars.extend(Self::positions(matcher));
+    }
+
+    /// The positions of the current fields of `matcher`, which are several if
+    /// siblings share a name, or the top level.
+    fn positions<'m>(matcher: &'m Matcher<'_>) -> impl Iterator<Item = Option<FieldId>> + 'm {
+        let fields = matcher.fields();
+        let top = fields.is_empty().then_some(None);
+        top.into_iter()
+            .chain(fields.iter().map(|field| Some(field.id())))
+    }
+
+    /// The topmost fields of `tree` which did not match.
+    fn unmatched<'t>(&self, tree: &'t Tree<'_>) -> Vec<Unmatched<'t>> {
+        tree.walk()
+            .filter(|field| !self.matched.contains(&field.id()))
+            .filter_map(|field| {
+                let parent = field.parent();
+                if parent
+                    .as_ref()
+                    .is_some_and(|parent| !self.matched.contains(&parent.id()))
+                {
+                    return None;
+                }
+                let position = parent.map(|parent| parent.id());
+                let reason = if self.objects.contains(&position) {
+                    Reason::MissingKey
+                } else if self.scalars.contains(&position) {
+                    Reason::NotAnObject
+                } else {
+                    // Only `null` or empty arrays, so there was nothing to match.
+                    return None;
+                };
+                Some(Unmatched { field, reason })
+            })
+            .collect()
+    }
+}
+
+fn apply_inner(
+    matcher: &mut Matcher<'_>,
+    value: &mut Value,
+    mode: Mode,
+    mut seen: Option<&mut Seen>,
+) -> Result<(), NotAnObject> {
+    match value {
+        Value::Object(object) => {
+            if let Some(seen) = seen.as_deref_mut() {
+                seen.object(matcher);
+            }
+            let mut result = Ok(());
+            object.retain(|key, child| {
+                if result.is_err() {
+                    return true;
+                }
+                let decision = matcher.enter(key);
+                if let Some(seen) = seen.as_deref_mut() {
+                    seen.key(matcher);
+                }
+                let keep = match decision {
+                    Decision::Include => true,
+                    Decision::Exclude => false,
+                    Decision::Partial => {
+                        result = apply_inner(matcher, child, mode, seen.as_deref_mut());
+                        true
+                    }
+                };
+                matcher.exit();
+                keep
+            });
+            result
+        }
+        Value::Array(elements) => elements
+            .iter_mut()
+            .try_for_each(|element| apply_inner(matcher, element, mode, seen.as_deref_mut())),
+        Value::Null => Ok(()),
+        _ => check_scalar(matcher, mode, seen),
+    }
+}
+
+fn project_inner(
+    matcher: &mut Matcher<'_>,
+    value: &Value,
+    mode: Mode,
+    mut seen: Option<&mut Seen>,
+) -> Result<Value, NotAnObject> {
+    match value {
+        Value::Object(object) => {
+            if let Some(seen) = seen.as_deref_mut() {
+                seen.object(matcher);
+            }
+            let mut projected = Map::new();
+            for (key, child) in object {
+                let decision = matcher.enter(key);
+                if let Some(seen) = seen.as_deref_mut() {
+                    seen.key(matcher);
+                }
+                match decision {
+                    Decision::Include => {
+                        projected.insert(key.clone(), child.clone());
+                    }
+                    Decision::Exclude => {}
+                    Decision::Partial => {
+                        let child = project_inner(matcher, child, mode, seen.as_deref_mut())?;
+                        projected.insert(key.clone(), child);
+                    }
+                }
+                matcher.exit();
+            }
+            Ok(Value::Object(projected))
+        }
+        Value::Array(elements) => elements
+            .iter()
+            .map(|element| project_inner(matcher, element, mode, seen.as_deref_mut()))
+            .collect(),
+        Value::Null => Ok(Value::Null),
+        _ => check_scalar(matcher, mode, seen).map(|()| value.clone()),
+    }
+}
+
+/// Handle a value which is neither an object, an array nor `null`, found
+/// where the children of the current field apply.
+fn check_scalar(
+    matcher: &Matcher<'_>,
+    mode: Mode,
+    seen: Option<&mut Seen>,
+) -> Result<(), NotAnObject> {
+    if let Some(seen) = seen {
+        seen.scalar(matcher);
+    }
+    match mode {
+        Mode::Lenient => Ok(()),
+        Mode::Strict => Err(NotAnObject {
+            path: matcher
+                .fields()
+                .first()
+                .map(FieldPath::from)
+                .unwrap_or_default(),
+        }),
+    }
+}
+
+/// Returned in [`Mode::Strict`] when a field with children applies to a value
+/// which is neither an object, an array nor `null`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NotAnObject {
+    /// The path of the field whose children cannot be applied. Empty if the
+    /// whole document is such a value.
+    pub path: FieldPath,
+}
+
+impl fmt::Display for NotAnObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cannot select fields from a non-object at {:?}",
+            self.path.to_string()
+        )
+    }
+}
+
+impl core::error::Error for NotAnObject {}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn document() -> Value {
+        json!({
+            "a": {"b": 1, "c": {"d": 2, "e": 3}},
+            "f": [1, 2],
+            "g": "h",
+        })
+    }
+
+    #[test]
+    fn test_allowlist() {
+        let tree = Tree::parse("(a(c(d),x),g,y(z))").unwrap();
+        let expected = json!({"a": {"c": {"d": 2}}, "g": "h"});
+        assert_eq!(project(&tree, &document()), expected);
+        let mut value = document();
+        apply(&tree, &mut value);
+        assert_eq!(value, expected);
+    }
+
+    #[test]
+    fn test_denylist() {
+        let tree = Tree::parse("!(a(c(d),x),g,y(z))").unwrap();
+        let expected = json!({"a": {"b": 1, "c": {"e": 3}}, "f": [1, 2]});
+        assert_eq!(project(&tree, &document()), expected);
+        let mut value = document();
+        apply(&tree, &mut value);
+        assert_eq!(value, expected);
+    }
+
+    #[test]
+    fn test_repeated_siblings() {
+        let value = json!({"a": {"b": 1, "c": 2, "d": 3}});
+        let tree = Tree::parse("(a(b),a(c))").unwrap();
+        let expected = json!({"a": {"b": 1, "c": 2}});
+        assert_eq!(project(&tree, &value), expected);
+        let mut applied = value.clone();
+        apply(&tree, &mut applied);
+        assert_eq!(applied, expected);
+
+        let tree = Tree::parse("!(a(b),a(c))").unwrap();
+        assert_eq!(project(&tree, &value), json!({"a": {"d": 3}}));
+    }
+
+    #[test]
+    fn test_arrays() {
+        let value = json!({
+            "items": [
+                {"id": 1, "price": 2, "tags": [[{"name": "x", "id": 3}]]},
+                [{"id": 4, "price": 5}],
+                null,
+            ],
+        });
+        let tree = Tree::parse("(items(id,tags(name)))").unwrap();
+        assert_eq!(
+            project(&tree, &value),
+            json!({"items": [
+                {"id": 1, "tags": [[{"name": "x"}]]},
+                [{"id": 4}],
+                null,
+            ]})
+        );
+        let tree = Tree::parse("!(items(price))").unwrap();
+        let mut projected = value.clone();
+        apply(&tree, &mut projected);
+        assert_eq!(projected["items"][1], json!([{"id": 4}]));
+        assert_eq!(
+            project(&tree, &json!([{"items": [1]}])),
+            json!([{"items": [1]}])
+        );
+    }
+
+    #[test]
+    fn test_modes() {
+        let tree = Tree::parse("(g(h))").unwrap();
+        assert_eq!(project(&tree, &document()), json!({"g": "h"}));
+        assert_eq!(project(&tree, &json!(1)), json!(1));
+
+        let error = project_with(&tree, &document(), Mode::Strict).unwrap_err();
+        assert_eq!(error.path.to_string(), "g");
+        let error = project_with(&tree, &json!([1]), Mode::Strict).unwrap_err();
+        assert!(error.path.is_empty());
+        let mut value = json!({"g": [null, {"h": 1, "i": 2}]});
+        apply_with(&tree, &mut value, Mode::Strict).unwrap();
+        assert_eq!(value, json!({"g": [null, {"h": 1}]}));
+    }
+
+    #[test]
+    fn test_reporting() {
+        let tree = Tree::parse("(a(b,c(x),y(z)),f(g),g(h),items(id,name),n(m),w(v))").unwrap();
+        let value = json!({
+            "a": {"b": 1, "c": {"d": 2}},
+            "f": [1, 2],
+            "g": "h",
+            "items": [{"id": 1}, [{"id": 2}], {"name": "x"}],
+            "n": null,
+            "w": [],
+        });
+        let (projected, unmatched) = project_reporting(&tree, &value);
+        let mut applied = value.clone();
+        let applied_unmatched = apply_reporting(&tree, &mut applied);
+        assert_eq!(projected, applied);
+        let report: Vec<_> = unmatched
+            .iter()
+            .map(|unmatched| (unmatched.field.path().join("."), unmatched.reason))
+            .collect();
+        assert_eq!(
+            report,
+            [
+                ("a.c.x".to_string(), Reason::MissingKey),
+                ("a.y".to_string(), Reason::MissingKey),
+                ("f.g".to_string(), Reason::NotAnObject),
+                ("g.h".to_string(), Reason::NotAnObject),
+            ]
+        );
+        assert_eq!(applied_unmatched.len(), unmatched.len());
+        let tree = Tree::parse("(a(b),a(c),a(d))").unwrap();
+        let unmatched = project_reporting(&tree, &json!({"a": {"b": 1, "c": 2}})).1;
+        assert_eq!(unmatched.len(), 1);
+        assert_eq!(unmatched[0].field.path(), ["a", "d"]);
+        assert_eq!(unmatched[0].reason, Reason::MissingKey);
+        let unmatched = project_reporting(&tree, &json!({"a": 1})).1;
+        assert_eq!(unmatched.len(), 3);
+        assert!(
+            unmatched
+                .iter()
+                .all(|unmatched| unmatched.reason == Reason::NotAnObject)
+        );
+
+        let tree = Tree::parse("!(a(x))").unwrap();
+        let unmatched = project_reporting(&tree, &json!(1)).1;
+        assert_eq!(unmatched[0].field.name(), "a");
+        assert_eq!(unmatched[0].reason, Reason::NotAnObject);
+    }
+}
@@ -63,20 +63,69 @@
 //! <digit>             ::= "0" | ... | "9"
 //! <negation>          ::= "!"
 //! ```
+//!
+//! # Features
+//!
+//! - `std` (default): Uses the standard library. Without it, the crate is
+//!   `no_std` and only depends on `alloc`.
+//! - `serde`: Implements `Serialize` and `Deserialize` for [`Tree`].
+//! - `serde_json`: Adds the `json` module for filtering `serde_json::Value`s.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
-mod parser;
-mod str_range;
+extern crate alloc;
+
+mod automaton;
+mod builder;
+mod edit;
+mod event;
+mod field_path;
+#[cfg(feature = "serde_json")]
+pub mod json;
+mod literal;
+mod matcher;
+pub mod parser;
+#[cfg(feature = "serde")]
+mod serde_impl;
+mod shared;
+#[cfg(test)]
+mod test_cases;
+pub mod token;
 mod tree;
+mod visit;
 
+pub use automaton::Automaton;
+pub use automaton::StateId;
+pub use builder::BuildError;
+pub use builder::TreeBuilder;
+pub use event::Event;
+pub use event::Events;
+pub use event::events;
+pub use field_path::FieldPath;
+pub use field_path::InvalidPath;
+pub use literal::is_valid_filter;
+pub use matcher::Matcher;
+pub use shared::SharedTree;
+pub use tree::Decision;
+pub use tree::DetachedTree;
+pub use tree::Edge;
 pub use tree::Field;
+pub use tree::FieldId;
+pub use tree::InvalidName;
+pub use tree::MismatchedBuffer;
+pub use tree::NoFields;
 pub use tree::Tree;
 pub use tree::Unparsable;
+pub use visit::Visit;
+pub use visit::VisitContext;
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    // Exercise the borrowed slices used before paths became generic.
     #[test]
+    #[allow(clippy::needless_borrows_for_generic_args)]
     fn test_can_index() {
         let tree = Tree::parse("(a(b,c(d)),e)").unwrap();
         assert!(!tree.negation());
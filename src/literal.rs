@@ -0,0 +1,119 @@
+//! This module contains the [`fields!`](crate::fields) macro and the `const`
+//! validation behind it, which reject malformed filter literals at compile
+//! time.
+
+/// Whether `s` is a valid filter string, i.e., whether
+/// [`Tree::parse`](crate::Tree::parse) would succeed.
+///
+/// Unlike `Tree::parse`, this can be evaluated in `const` contexts. See
+/// [`fields!`](crate::fields).
+///
+/// # Example
+///
+/// ```
+/// const VALID: bool = z157::is_valid_filter("!(a(b),c)");
+/// assert!(VALID);
+/// assert!(!z157::is_valid_filter("(a(b),)"));
+/// ```
+#[must_use]
+pub const fn is_valid_filter(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    let len = bytes.len();
+    let mut i = 0;
+    if i < len && bytes[i] == b'!' {
+        i += 1;
+    }
+    if i >= len || bytes[i] != b'(' {
+        return false;
+    }
+    i += 1;
+    let mut depth = 1;
+    loop {
+        // A field name is expected after every `(` and `,`.
+        let start = i;
+        while i < len && is_name_byte(bytes[i]) {
+            i += 1;
+        }
+        if i == start || i >= len {
+            return false;
+        }
+        match bytes[i] {
+            b'(' => depth += 1,
+            b',' => {}
+            b')' => loop {
+                depth -= 1;
+                i += 1;
+                if depth == 0 {
+                    return i == len;
+                }
+                if i >= len {
+                    return false;
+                }
+                match bytes[i] {
+                    b')' => {}
+                    b',' => break,
+                    _ => return false,
+                }
+            },
+            _ => return false,
+        }
+        i += 1;
+    }
+}
+
+/// Whether `b` may be part of a field name.
+const fn is_name_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'-' || b == b'_'
+}
+
+/// Create a [`Tree`](crate::Tree) from a filter string literal which is
+/// validated at compile time.
+///
+/// The argument must be a `const` expression of type `&'static str`. The
+/// result is a `Tree<'static>` which borrows the literal.
+///
+/// # Example
+///
+/// ```
+/// let tree = z157::fields!("(name,address(city))");
+/// assert!(tree.index(&["address", "city"]).is_some());
+/// ```
+///
+/// A malformed filter string is a compile error:
+///
+/// ```compile_fail
+/// let tree = z157::fields!("(name,address(city)");
+/// ```
+#[macro_export]
+macro_rules! fields {
+    ($filter:expr $(,)?) => {{
+        const FILTER: &'static str = $filter;
+        const _: () = ::core::assert!($crate::is_valid_filter(FILTER), "invalid filter string");
+        $crate::Tree::parse(FILTER).expect("the filter string is validated at compile time")
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_cases::INVALID_FILTERS;
+    use crate::test_cases::VALID_FILTERS;
+
+    #[test]
+    fn test_filters() {
+        for &s in VALID_FILTERS {
+            assert!(is_valid_filter(s), "{s:?}");
+        }
+        for &s in INVALID_FILTERS {
+            assert!(!is_valid_filter(s), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn test_fields() {
+        const DEFAULT: &str = "!(secret(key))";
+        let tree = crate::fields!(DEFAULT);
+        assert!(tree.negation());
+        assert_eq!(tree.to_string(), DEFAULT);
+    }
+}
@@ -0,0 +1,186 @@
+//! This module contains the [`Matcher`], which decides on fields one key at a
+//! time while walking a nested document.
+
+use alloc::vec::Vec;
+
+use crate::tree::Decision;
+use crate::tree::Field;
+use crate::tree::Tree;
+
+/// Tracks a position in a nested document and decides whether the keys found
+/// there are selected by a [`Tree`].
+///
+/// Call [`enter`](Matcher::enter) when descending into a key and
+/// [`exit`](Matcher::exit) when leaving it again. The matcher remembers the
+/// matching [`Field`]s of every entered level, which is more than one only
+/// where siblings share a name, and how many levels have been entered below
+/// the deepest of them. Its buffers are reused, so no paths are allocated
+/// along the way.
+///
+/// The decisions are the same as those given by [`Tree::decide`] for the path
+/// of entered keys.
+///
+/// # Example
+///
+/// ```
+/// use z157::Decision;
+/// use z157::Tree;
+///
+/// let tree = Tree::parse("(name,address(city))").unwrap();
+/// let mut matcher = tree.matcher();
+/// assert_eq!(matcher.enter("address"), Decision::Partial);
+/// assert_eq!(matcher.enter("city"), Decision::Include);
+/// matcher.exit();
+/// assert_eq!(matcher.enter("zip"), Decision::Exclude);
+/// matcher.exit();
+/// matcher.exit();
+/// assert_eq!(matcher.enter("name"), Decision::Include);
+/// ```
+#[derive(Clone)]
+pub struct Matcher<'t> {
+    tree: &'t Tree<'t>,
+    /// The matching fields of all entered levels which are part of the tree,
+    /// one level after the other.
+    fields: Vec<Field<'t>>,
+    /// The offset in `fields` at which each level starts.
+    levels: Vec<usize>,
+    /// The number of entered levels below the deepest level in `fields`.
+    beyond: usize,
+}
+
+impl<'t> Matcher<'t> {
+    /// Create a [`Matcher`] positioned at the top level of a document.
+    #[must_use]
+    pub fn new(tree: &'t Tree<'t>) -> Self {
+        Self {
+            tree,
+            fields: Vec::new(),
+            levels: Vec::new(),
+            beyond: 0,
+        }
+    }
+
+    /// Descend into the key `name` and decide whether it is selected.
+    pub fn enter(&mut self, name: &str) -> Decision {
+        if self.beyond > 0 || self.at_leaf() {
+            self.beyond += 1;
+            return self.decision();
+        }
+        let start = self.fields.len();
+        match self.levels.last() {
+            Some(&level) => {
+                for index in level..start {
+                    let field = self.fields[index].clone();
+                    self.fields.extend(field.children_named(name));
+                }
+            }
+            None => self.fields.extend(self.tree.top_named(name)),
+        }
+        if self.fields.len() == start {
+            self.beyond = 1;
+        } else {
+            self.levels.push(start);
+        }
+        self.decision()
+    }
+
+    /// Leave the most recently entered key.
+    ///
+    /// Exiting at the top level has no effect.
+    pub fn exit(&mut self) {
+        if self.beyond > 0 {
+            self.beyond -= 1;
+        } else if let Some(level) = self.levels.pop() {
+            self.fields.truncate(level);
+        }
+    }
+
+    /// The decision for the current position.
+    #[must_use]
+    pub fn decision(&self) -> Decision {
+        let negation = self.tree.negation();
+        if self.at_leaf() {
+            if negation {
+                Decision::Exclude
+            } else {
+                Decision::Include
+            }
+        } else if self.beyond > 0 {
+            if negation {
+                Decision::Include
+            } else {
+                Decision::Exclude
+            }
+        } else {
+            Decision::Partial
+        }
+    }
+
+    /// The fields of the tree at the current position. Empty at the top level
+    /// and outside of the tree.
+    #[cfg_attr(not(feature = "serde_json"), allow(dead_code))]
+    pub(crate) fn fields(&self) -> &[Field<'t>] {
+        match self.levels.last() {
+            Some(&level) if self.beyond == 0 => &self.fields[level..],
+            _ => &[],
+        }
+    }
+
+    /// Whether one of the fields of the deepest level in the tree is a leaf.
+    fn at_leaf(&self) -> bool {
+        self.levels.last().is_some_and(|&level| {
+            self.fields[level..]
+                .iter()
+                .any(|field| !field.has_children())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_cases::PATHS;
+    use crate::test_cases::VALID_FILTERS;
+
+    #[test]
+    fn test_matches_decide() {
+        for &filter in VALID_FILTERS {
+            let tree = Tree::parse(filter).unwrap();
+            for &path in PATHS {
+                let mut matcher = tree.matcher();
+                let mut decision = matcher.decision();
+                for element in path {
+                    decision = matcher.enter(element);
+                }
+                assert_eq!(decision, tree.decide(path), "{filter} {path:?}");
+                for _ in path {
+                    matcher.exit();
+                }
+                assert_eq!(matcher.decision(), Decision::Partial);
+            }
+        }
+    }
+
+    #[test]
+    fn test_repeated_siblings() {
+        let tree = Tree::parse("(a(b),a(c))").unwrap();
+        let mut matcher = tree.matcher();
+        assert_eq!(matcher.enter("a"), Decision::Partial);
+        assert_eq!(matcher.fields().len(), 2);
+        assert_eq!(matcher.enter("c"), Decision::Include);
+        matcher.exit();
+        assert_eq!(matcher.enter("b"), Decision::Include);
+        matcher.exit();
+        matcher.exit();
+        assert!(matcher.fields().is_empty());
+        assert_eq!(matcher.enter("c"), Decision::Exclude);
+    }
+
+    #[test]
+    fn test_exit_at_top_level() {
+        let tree = Tree::parse("(a)").unwrap();
+        let mut matcher = tree.matcher();
+        matcher.exit();
+        assert_eq!(matcher.enter("a"), Decision::Include);
+    }
+}
@@ -1,17 +1,39 @@
-// <fields>            ::= [ <negation> ] <fields_struct>
-// <fields_struct>     ::= "(" <field_items> ")"
-// <field_items>       ::= <field> [ "," <field_items> ]
-// <field>             ::= <field_name> | <fields_substruct>
-// <fields_substruct>  ::= <field_name> <fields_struct>
-// <field_name>        ::= <dash_letter_digit> [ <field_name> ]
-// <dash_letter_digit> ::= <dash> | <letter> | <digit>
-// <dash>              ::= "-" | "_"
-// <letter>            ::= "A" | ... | "Z" | "a" | ... | "z"
-// <digit>             ::= "0" | ... | "9"
-// <negation>          ::= "!"
-
-use std::fmt;
+//! The syntax tree of a filter string, which mirrors the grammar in the
+//! [crate documentation](crate#specification).
+//!
+//! Most users should prefer [`Tree`](crate::Tree). This module is meant for
+//! tools which need the exact structure of a filter string and the location
+//! of each part of it, such as linters. For input which may be invalid, e.g.,
+//! when highlighting syntax while the filter is typed, see
+//! [`token`](crate::token).
+//!
+//! # Example
+//!
+//! ```
+//! use z157::parser::Field;
+//!
+//! let fields = z157::parser::parse("!(a(b),c)").unwrap();
+//! assert!(fields.negation);
+//! assert_eq!(fields.fields_struct.span, 1..9);
+//! let Field::FieldsSubstruct(a) =
+//!     &fields.fields_struct.field_items.0[0]
+//! else {
+//!     panic!("a has children");
+//! };
+//! assert_eq!(a.field_name.name, "a");
+//! assert_eq!(a.field_name.span, 2..3);
+//! assert_eq!(a.fields_struct.span, 3..6);
+//! ```
 
+#![warn(missing_docs)]
+
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+use winnow::LocatingSlice;
 use winnow::ModalResult;
 use winnow::Parser;
 use winnow::combinator::alt;
@@ -20,14 +42,49 @@ use winnow::combinator::opt;
 use winnow::combinator::separated;
 use winnow::token::take_while;
 
+/// The input of the parsers, which keeps track of locations.
+type Input<'s> = LocatingSlice<&'s str>;
+
+/// Parse all of `s` into a syntax tree.
+///
+/// # Errors
+///
+/// Returns an error if `s` does not match the expected format.
+pub fn parse(s: &str) -> Result<Fields<'_>, Error> {
+    Fields::try_from(s)
+}
+
+/// `<fields> ::= [ <negation> ] <fields_struct>`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub struct Fields<'s> {
+    /// The top-level struct.
     pub fields_struct: FieldsStruct<'s>,
+    /// Whether the struct is preceded by `!`, i.e., whether the fields are a
+    /// denylist.
     pub negation: bool,
 }
 
+/// Returned when a string does not match the expected format.
 #[derive(Debug)]
 pub struct Error {
     parse_error_message: String,
+    offset: usize,
+}
+
+impl Error {
+    pub(crate) fn new(parse_error_message: String, offset: usize) -> Self {
+        Self {
+            parse_error_message,
+            offset,
+        }
+    }
+
+    /// The byte offset in the parsed string at which parsing failed.
+    #[must_use]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
 }
 
 impl fmt::Display for Error {
@@ -36,42 +93,89 @@ impl fmt::Display for Error {
     }
 }
 
-impl std::error::Error for Error {}
+impl core::error::Error for Error {}
 
 impl<'s> TryFrom<&'s str> for Fields<'s> {
     type Error = Error;
 
     fn try_from(value: &'s str) -> Result<Self, Self::Error> {
-        Self::parse.parse(value).map_err(|parse_error| Error {
-            parse_error_message: parse_error.to_string(),
-        })
+        Self::parse
+            .parse(LocatingSlice::new(value))
+            .map_err(|parse_error| Error {
+                parse_error_message: parse_error.to_string(),
+                offset: parse_error.offset(),
+            })
     }
 }
 
-pub struct FieldsStruct<'s>(pub FieldItems<'s>);
-pub struct FieldItems<'s>(pub Vec<Field<'s>>);
+/// `<fields_struct> ::= "(" <field_items> ")"`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FieldsStruct<'s> {
+    /// The fields between the parentheses.
+    pub field_items: FieldItems<'s>,
+    /// The location of the struct in the parsed string, including the
+    /// parentheses.
+    pub span: Range<usize>,
+}
+
+/// `<field_items> ::= <field> [ "," <field_items> ]`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FieldItems<'s>(
+    /// The fields in the order in which they are written. Never empty.
+    pub Vec<Field<'s>>,
+);
+
+/// `<field> ::= <field_name> | <fields_substruct>`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum Field<'s> {
+    /// A field with children, such as `a(b)`.
     FieldsSubstruct(FieldsSubstruct<'s>),
+    /// A field without children, such as `b`.
     FieldName(FieldName<'s>),
 }
+
+/// `<fields_substruct> ::= <field_name> <fields_struct>`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub struct FieldsSubstruct<'s> {
+    /// The name of the field.
     pub field_name: FieldName<'s>,
+    /// The children of the field.
     pub fields_struct: FieldsStruct<'s>,
 }
 
-#[derive(Debug)]
-pub struct FieldName<'s>(pub &'s str);
+/// `<field_name> ::= <dash_letter_digit> [ <field_name> ]`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FieldName<'s> {
+    /// The name as written, which equals `&s[span]` for the parsed `s`.
+    pub name: &'s str,
+    /// The location of the name in the parsed string.
+    pub span: Range<usize>,
+}
+
+impl FieldName<'_> {
+    /// Whether all of `s` is a single field name.
+    #[must_use]
+    pub fn is_valid(s: &str) -> bool {
+        FieldName::parse.parse(LocatingSlice::new(s)).is_ok()
+    }
+}
 
 impl<'s> FieldName<'s> {
-    fn parse(input: &mut &'s str) -> ModalResult<Self> {
-        let field_name =
-            take_while(1.., ('-', '_', 'A'..='Z', 'a'..='z', '0'..='9')).parse_next(input)?;
-        Ok(Self(field_name))
+    fn parse(input: &mut Input<'s>) -> ModalResult<Self> {
+        let (name, span) = take_while(1.., ('-', '_', 'A'..='Z', 'a'..='z', '0'..='9'))
+            .with_span()
+            .parse_next(input)?;
+        Ok(Self { name, span })
     }
 }
 
 impl<'s> FieldsSubstruct<'s> {
-    fn parse(input: &mut &'s str) -> ModalResult<Self> {
+    fn parse(input: &mut Input<'s>) -> ModalResult<Self> {
         let field_name = FieldName::parse.parse_next(input)?;
         let fields_struct = FieldsStruct::parse.parse_next(input)?;
         Ok(Self {
@@ -82,7 +186,7 @@ impl<'s> FieldsSubstruct<'s> {
 }
 
 impl<'s> Field<'s> {
-    fn parse(input: &mut &'s str) -> ModalResult<Self> {
+    fn parse(input: &mut Input<'s>) -> ModalResult<Self> {
         alt((
             FieldsSubstruct::parse.map(Self::FieldsSubstruct),
             FieldName::parse.map(Self::FieldName),
@@ -92,20 +196,21 @@ impl<'s> Field<'s> {
 }
 
 impl<'s> FieldItems<'s> {
-    fn parse(input: &mut &'s str) -> ModalResult<Self> {
+    fn parse(input: &mut Input<'s>) -> ModalResult<Self> {
         Ok(Self(separated(1.., Field::parse, ',').parse_next(input)?))
     }
 }
 impl<'s> FieldsStruct<'s> {
-    fn parse(input: &mut &'s str) -> ModalResult<Self> {
-        Ok(Self(
-            delimited('(', FieldItems::parse, ')').parse_next(input)?,
-        ))
+    fn parse(input: &mut Input<'s>) -> ModalResult<Self> {
+        let (field_items, span) = delimited('(', FieldItems::parse, ')')
+            .with_span()
+            .parse_next(input)?;
+        Ok(Self { field_items, span })
     }
 }
 
 impl<'s> Fields<'s> {
-    fn parse(input: &mut &'s str) -> ModalResult<Self> {
+    fn parse(input: &mut Input<'s>) -> ModalResult<Self> {
         let negation = opt('!').parse_next(input)?.is_some();
         let fields_struct = FieldsStruct::parse.parse_next(input)?;
         Ok(Self {
@@ -118,12 +223,10 @@ impl<'s> Fields<'s> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_cases::INVALID_FILTERS;
+    use crate::test_cases::VALID_FILTERS;
     #[test]
     fn test_field_name() {
-        // clippy or rustc seem to have a false positive on code like
-        // let input = &mut (s.clone()); below.
-        #![allow(noop_method_call)]
-
         const VALID: &[&str] = &[
             "a",
             "A",
@@ -135,14 +238,15 @@ mod tests {
         const INVALID: &[&str] = &["", "!", "abc/"];
 
         for &s in VALID {
-            let input = &mut (s.clone());
+            let input = &mut LocatingSlice::new(s);
             let field_name = FieldName::parse(input).unwrap();
-            assert_eq!(s, field_name.0);
+            assert_eq!(s, field_name.name);
+            assert_eq!(0..s.len(), field_name.span);
             assert!(input.is_empty());
         }
 
         for &s in INVALID {
-            let input = &mut (s.clone());
+            let input = &mut LocatingSlice::new(s);
             let field_name = FieldName::parse(input);
             assert!(
                 !input.is_empty() || field_name.is_err(),
@@ -156,23 +260,42 @@ mod tests {
         let s = "!(field_a)";
         let fields: Fields = s.try_into().unwrap();
         assert!(fields.negation);
-        let field_items = fields.fields_struct.0.0;
+        let field_items = fields.fields_struct.field_items.0;
         assert_eq!(field_items.len(), 1);
         assert!(matches!(
             field_items[0],
-            Field::FieldName(FieldName("field_a"))
+            Field::FieldName(FieldName {
+                name: "field_a",
+                ..
+            })
         ));
 
         let s = "(field_a(field_b,field_c(field_d)),field_d)";
         let fields: Fields = s.try_into().unwrap();
         assert!(!fields.negation);
-        let field_items = fields.fields_struct.0.0;
+        assert_eq!(fields.fields_struct.span, 0..s.len());
+        let field_items = fields.fields_struct.field_items.0;
         assert_eq!(field_items.len(), 2);
-        assert!(matches!(field_items[0], Field::FieldsSubstruct(_)));
-        assert!(matches!(
-            field_items[1],
-            Field::FieldName(FieldName("field_d"))
-        ));
+        let Field::FieldsSubstruct(substruct) = &field_items[0] else {
+            panic!("field_a is a substruct");
+        };
+        assert_eq!(substruct.field_name.span, 1..8);
+        assert_eq!(substruct.fields_struct.span, 8..34);
+        let Field::FieldName(field_d) = &field_items[1] else {
+            panic!("field_d is a name");
+        };
+        assert_eq!(field_d.name, "field_d");
+        assert_eq!(&s[field_d.span.clone()], "field_d");
+    }
+
+    #[test]
+    fn test_filters() {
+        for &s in VALID_FILTERS {
+            assert!(Fields::try_from(s).is_ok(), "{s:?}");
+        }
+        for &s in INVALID_FILTERS {
+            assert!(Fields::try_from(s).is_err(), "{s:?}");
+        }
     }
 
     #[test]
@@ -183,6 +306,6 @@ mod tests {
 
         let s = "(a())";
         let result = Fields::try_from(s);
-        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().offset(), 2);
     }
 }
@@ -0,0 +1,170 @@
+//! This module implements [`Serialize`] and [`Deserialize`] for [`Tree`].
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+use serde::de;
+
+use crate::builder;
+use crate::builder::Node;
+use crate::tree::Tree;
+
+/// Serializes the filter string, as written by [`Display`](fmt::Display).
+impl Serialize for Tree<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Deserializes either a filter string or a nested map of field names.
+///
+/// In the map form, every key is a field name, and its value is either a map
+/// of its children, or an empty map, unit or `null` for a leaf. The map form
+/// always results in an allowlist. Formats which are not human readable, such
+/// as bincode, cannot tell the two forms apart and only support the string.
+///
+/// # Example
+///
+/// ```
+/// use z157::Tree;
+///
+/// let tree: Tree =
+///     serde_json::from_str(r#""!(name,address(city))""#)
+///         .unwrap();
+/// assert!(tree.negation());
+///
+/// let tree: Tree = serde_json::from_str(
+///     r#"{"name": {}, "address": {"city": null}}"#,
+/// )
+/// .unwrap();
+/// assert_eq!(tree.to_string(), "(name,address(city))");
+/// ```
+impl<'de> Deserialize<'de> for Tree<'static> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(TreeVisitor)
+        } else {
+            deserializer.deserialize_string(TreeVisitor)
+        }
+    }
+}
+
+struct TreeVisitor;
+
+impl<'de> de::Visitor<'de> for TreeVisitor {
+    type Value = Tree<'static>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a filter string or a map of field names")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Tree::parse(v)
+            .map(Tree::into_owned)
+            .map_err(de::Error::custom)
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+        Tree::parse(v).map_err(de::Error::custom)
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        let Children(nodes) = ChildrenVisitor.visit_map(map)?;
+        builder::validate(&nodes).map_err(de::Error::custom)?;
+        builder::write(&nodes, false).map_err(de::Error::custom)
+    }
+}
+
+/// The children of a field in the map form.
+struct Children(Vec<Node>);
+
+impl<'de> Deserialize<'de> for Children {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ChildrenVisitor)
+    }
+}
+
+struct ChildrenVisitor;
+
+impl<'de> de::Visitor<'de> for ChildrenVisitor {
+    type Value = Children;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a map of field names, or null")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(Children(Vec::new()))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(Children(Vec::new()))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut nodes = Vec::new();
+        while let Some((name, Children(children))) = map.next_entry::<String, Children>()? {
+            nodes.push(Node { name, children });
+        }
+        Ok(Children(nodes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Tree;
+
+    #[test]
+    fn test_from_string() {
+        let tree: Tree = serde_json::from_str(r#""!(a(b),c)""#).unwrap();
+        assert!(tree.negation());
+        assert_eq!(serde_json::to_string(&tree).unwrap(), r#""!(a(b),c)""#);
+        assert!(serde_json::from_str::<Tree>(r#""(a,)""#).is_err());
+    }
+
+    #[test]
+    fn test_from_map() {
+        let tree: Tree = serde_json::from_str(r#"{"a": {"b": {}, "c": null}, "d": {}}"#).unwrap();
+        assert!(!tree.negation());
+        assert_eq!(tree.to_string(), "(a(b,c),d)");
+        for invalid in [
+            r#"{"a.b": {}}"#,
+            r#"{"a": {"": {}}}"#,
+            r#"{"a": 1}"#,
+            "1",
+            "{}",
+            r#""()""#,
+            r#""!()""#,
+        ] {
+            assert!(serde_json::from_str::<Tree>(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_not_human_readable() {
+        let tree = Tree::parse("!(a(b),c)").unwrap();
+        let bytes = bincode::serialize(&tree).unwrap();
+        let tree: Tree = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(tree.to_string(), "!(a(b),c)");
+        let bytes = bincode::serialize("(a,)").unwrap();
+        assert!(bincode::deserialize::<Tree>(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_optional_field() {
+        #[derive(serde::Deserialize)]
+        struct Query {
+            fields: Option<Tree<'static>>,
+        }
+
+        let query: Query = serde_json::from_str(r#"{"fields": "(a)"}"#).unwrap();
+        assert!(query.fields.unwrap().index(["a"]).is_some());
+        let query: Query = serde_json::from_str("{}").unwrap();
+        assert!(query.fields.is_none());
+    }
+}
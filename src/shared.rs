@@ -0,0 +1,86 @@
+//! This module contains [`SharedTree`], a [`Tree`] which can be cloned cheaply
+//! and shared between threads.
+
+use alloc::sync::Arc;
+use core::fmt;
+use core::ops::Deref;
+
+use crate::tree::Tree;
+
+/// A [`Tree`] which owns its buffer behind an [`Arc`].
+///
+/// Cloning a `SharedTree` only increments a reference count, and it is
+/// `Send + Sync + 'static`, so it can be stored in request extensions or
+/// handed to spawned tasks. It dereferences to [`Tree`], which provides
+/// methods such as [`walk`](Tree::walk) and [`index`](Tree::index).
+///
+/// # Example
+///
+/// ```
+/// use z157::SharedTree;
+/// use z157::Tree;
+///
+/// let shared = SharedTree::new(
+///     Tree::parse("(name,address(city))").unwrap(),
+/// );
+/// let handle = std::thread::spawn({
+///     let shared = shared.clone();
+///     move || shared.walk().count()
+/// });
+/// assert_eq!(handle.join().unwrap(), 3);
+/// assert!(shared.index(&["address", "city"]).is_some());
+/// ```
+#[derive(Clone)]
+pub struct SharedTree(Arc<Tree<'static>>);
+
+impl SharedTree {
+    /// Create a [`SharedTree`], cloning the buffer of `tree` if it is
+    /// borrowed.
+    #[must_use]
+    pub fn new(tree: Tree<'_>) -> Self {
+        Self(Arc::new(tree.into_owned()))
+    }
+}
+
+impl Deref for SharedTree {
+    type Target = Tree<'static>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<Tree<'_>> for SharedTree {
+    fn from(tree: Tree<'_>) -> Self {
+        Self::new(tree)
+    }
+}
+
+impl fmt::Display for SharedTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_send_sync_static() {
+        fn assert_send_sync_static<T: Send + Sync + 'static>() {}
+        assert_send_sync_static::<SharedTree>();
+    }
+
+    #[test]
+    fn test_clone_shares_tree() {
+        let buffer = String::from("!(a(b),c)");
+        let shared = SharedTree::from(Tree::parse(buffer.as_str()).unwrap());
+        drop(buffer);
+        let clone = shared.clone();
+        assert!(Arc::ptr_eq(&shared.0, &clone.0));
+        assert!(clone.negation());
+        assert_eq!(clone.to_string(), "!(a(b),c)");
+        assert_eq!(clone.leaves().count(), 2);
+    }
+}
@@ -0,0 +1,43 @@
+//! This module contains inputs which are shared by the tests of the different
+//! ways to parse and match filters, so that they are all checked against the
+//! same cases.
+
+/// Filters which every parser must accept.
+pub const VALID_FILTERS: &[&str] = &[
+    "(a)",
+    "(-_A9)",
+    "(a(b(c)))",
+    "(a(b(c)),d)",
+    "(a(b(c),d),e)",
+    "!(a(b(c),d),e)",
+    "!(a(b,c(d)),e)",
+    "(e,a(d,b(c)))",
+    "!(e,a(d,b(c)))",
+    "(a(b(c)),e,a(d))",
+    "(a(b(c)),a,e,a(d))",
+    "!(a(b(c)),a,e)",
+    "(a(b),a(x(y)),e,a(b(c)),a(d))",
+    "!(a(b),a(b(c),x),e,a(d))",
+];
+
+/// Filters which every parser must reject.
+pub const INVALID_FILTERS: &[&str] = &[
+    "", "!", "(", "!!(a)", "()", "!()", "(a,)", "(,a)", "(a(),b)", "(a(b)c)", "(a(b)", "(a))",
+    "(a)(b)", "(a b)", "(a.b)", "(é)", " (a)", "(a)!",
+];
+
+/// Paths to decide for each of the [`VALID_FILTERS`].
+pub const PATHS: &[&[&str]] = &[
+    &[],
+    &["a"],
+    &["a", "b"],
+    &["a", "b", "c"],
+    &["a", "b", "c", "x", "y"],
+    &["a", "d"],
+    &["a", "x"],
+    &["a", "x", "y"],
+    &["e"],
+    &["e", "x"],
+    &["x"],
+    &["x", "a"],
+];
@@ -0,0 +1,147 @@
+//! This module splits a filter string into [`Token`]s, e.g., for syntax
+//! highlighting.
+//!
+//! Unlike [`parser`](crate::parser), tokenizing never fails. Characters which
+//! cannot be part of a filter string become [`TokenKind::Invalid`] tokens, so
+//! that incomplete or malformed input can still be highlighted.
+
+#![warn(missing_docs)]
+
+use core::ops::Range;
+
+/// The kind of a [`Token`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TokenKind {
+    /// `(`, which opens a struct.
+    LParen,
+    /// `)`, which closes a struct.
+    RParen,
+    /// `,`, which separates fields.
+    Comma,
+    /// `!`, which negates the fields.
+    Bang,
+    /// A field name.
+    Name,
+    /// A single character which is not part of the grammar.
+    Invalid,
+}
+
+/// A piece of a filter string along with its location.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Token<'s> {
+    /// What the token is.
+    pub kind: TokenKind,
+    /// The text of the token, which equals `&s[span]` for the tokenized `s`.
+    pub text: &'s str,
+    /// The location of the token in the tokenized string.
+    pub span: Range<usize>,
+}
+
+/// Split `s` into [`Token`]s.
+///
+/// The tokens cover all of `s` without gaps, in order.
+///
+/// # Example
+///
+/// ```
+/// use z157::token::TokenKind;
+///
+/// let kinds: Vec<_> = z157::token::tokenize("!(a,b c)")
+///     .map(|token| token.kind)
+///     .collect();
+/// assert_eq!(
+///     kinds,
+///     [
+///         TokenKind::Bang,
+///         TokenKind::LParen,
+///         TokenKind::Name,
+///         TokenKind::Comma,
+///         TokenKind::Name,
+///         TokenKind::Invalid,
+///         TokenKind::Name,
+///         TokenKind::RParen,
+///     ]
+/// );
+/// ```
+#[must_use]
+pub fn tokenize(s: &str) -> Tokens<'_> {
+    Tokens { s, offset: 0 }
+}
+
+/// An iterator over the [`Token`]s of a string. See [`tokenize`].
+#[derive(Clone, Debug)]
+pub struct Tokens<'s> {
+    s: &'s str,
+    offset: usize,
+}
+
+impl Tokens<'_> {
+    /// The byte offset at which the next token starts.
+    pub(crate) fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl<'s> Iterator for Tokens<'s> {
+    type Item = Token<'s>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.s[self.offset..];
+        let c = rest.chars().next()?;
+        let (kind, len) = match c {
+            '(' => (TokenKind::LParen, 1),
+            ')' => (TokenKind::RParen, 1),
+            ',' => (TokenKind::Comma, 1),
+            '!' => (TokenKind::Bang, 1),
+            c if is_name_char(c) => (
+                TokenKind::Name,
+                rest.find(|c| !is_name_char(c)).unwrap_or(rest.len()),
+            ),
+            c => (TokenKind::Invalid, c.len_utf8()),
+        };
+        let span = self.offset..self.offset + len;
+        self.offset = span.end;
+        Some(Token {
+            kind,
+            text: &self.s[span.clone()],
+            span,
+        })
+    }
+}
+
+impl core::iter::FusedIterator for Tokens<'_> {}
+
+/// Whether `c` may be part of a field name.
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spans_cover_input() {
+        for s in ["", "(a(b,c),d)", "!(a-b_C9)", "(é,ü)", "((("] {
+            let mut end = 0;
+            for token in tokenize(s) {
+                assert_eq!(token.span.start, end);
+                assert_eq!(&s[token.span.clone()], token.text);
+                end = token.span.end;
+            }
+            assert_eq!(end, s.len());
+        }
+    }
+
+    #[test]
+    fn test_invalid_per_char() {
+        let tokens: Vec<_> = tokenize("a.é").collect();
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[0].kind, TokenKind::Name);
+        assert_eq!(tokens[1].kind, TokenKind::Invalid);
+        assert_eq!(tokens[2].kind, TokenKind::Invalid);
+        assert_eq!(tokens[2].span, 2..4);
+    }
+}
@@ -1,23 +1,106 @@
-use std::borrow::Cow;
-use std::fmt;
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
 
+use crate::automaton::Automaton;
+use crate::matcher::Matcher;
 use crate::parser;
-use crate::str_range::StrRange;
 
 /// Contains a tree of references to fields parsed from a filter string.
 ///
 /// This struct is not so useful on its own. [Attaching](DetachedTree::attach)
 /// the `DetachedTree` to a buffer via [`Tree`] will allow useful operations
-/// such as indexing and walking.
+/// such as indexing and walking. Detaching allows caching parsed structures
+/// separately from the strings they were parsed from.
 ///
-/// See usage examples in the [crate documentation](crate).
+/// # Example
+///
+/// ```
+/// use z157::Tree;
+///
+/// let (buffer, detached) =
+///     Tree::parse("(a(b))").unwrap().detach();
+/// let tree = detached.clone().attach(buffer).unwrap();
+/// assert!(tree.index(&["a", "b"]).is_some());
+/// assert!(detached.attach("(a(c))").is_err());
+/// ```
 #[derive(Debug, Clone)]
-struct DetachedTree {
-    /// Contains _free_ references to a string buffer. These are just offsets
-    /// and lengths, not actual pointers.
-    tree: ego_tree::Tree<StrRange>,
+pub struct DetachedTree {
+    /// The fields in pre-order, i.e., every field is directly followed by its
+    /// descendants.
+    nodes: Vec<Node>,
     /// Whether this tree was parsed as a denylist.
     negation: bool,
+    /// The children of fields with at least [`WIDE`] children. Keyed by the
+    /// index of the field, or [`NONE`] for the top level.
+    wide_children: BTreeMap<u32, WideChildren>,
+    /// The length of the parsed buffer.
+    len: usize,
+    /// The [`fingerprint`] of the parsed buffer.
+    fingerprint: u64,
+}
+
+/// A field in a [`DetachedTree`].
+///
+/// Contains a _free_ reference to a string buffer. These are just an offset
+/// and a length, not an actual pointer.
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    /// The offset of the field name in the buffer.
+    start: u32,
+    /// The length of the field name.
+    len: u32,
+    /// The index following the last descendant of this field.
+    end: u32,
+    /// The index of the parent of this field, or [`NONE`] at the top level.
+    parent: u32,
+    /// The number of parents of this field.
+    depth: u32,
+}
+
+impl Node {
+    /// The location of the field name in the buffer.
+    fn range(self) -> Range<usize> {
+        let start = self.start as usize;
+        start..start + self.len as usize
+    }
+}
+
+/// The children of a field in a [`DetachedTree`] with at least [`WIDE`]
+/// children, which are too many to search one by one.
+#[derive(Clone, Debug)]
+struct WideChildren {
+    /// Sorted by name, for looking up children by name.
+    by_name: Box<[u32]>,
+    /// In order, for finding the child whose subtree contains a field.
+    in_order: Box<[u32]>,
+}
+
+/// Stands in for the parent of top-level fields.
+const NONE: u32 = u32::MAX;
+
+/// The number of children from which looking up children by name is done by
+/// binary search rather than linear search.
+const WIDE: usize = 16;
+
+pub(crate) fn to_u32(n: usize) -> u32 {
+    u32::try_from(n).expect("trees are smaller than 4 GiB")
+}
+
+/// Hash `s` with 64-bit FNV-1a, which is fast for short strings.
+fn fingerprint(s: &str) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    s.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
 }
 
 impl DetachedTree {
@@ -25,27 +108,29 @@ impl DetachedTree {
     /// reference, which allows useful operations such as walking and
     /// indexing.
     ///
-    /// # Warning
-    ///
-    /// It is entirely possible to attach this `DetachedTree` to a _different_
-    /// string than the one used to parse the `DetachedTree`. This is
-    /// immediately a mistake, and should not be done. It is _safe_ to do
-    /// so, i.e., no undefined behavior will happen. However, the resulting
-    /// fields will not match whatever was parsed. If a shorter string is
-    /// attached, dereferencing a [`Field`] will likely cause a panic. Similar
-    /// if either string is non-ASCII, since `Field` checks the substring's
-    /// UTF8-compliance.
-    ///
-    /// A low-cost way of ensuring the same string is used for parsing and
-    /// attaching has not yet been found. However, by sticking to
-    /// [`parse`](DetachedTree::parse) and refraining from
-    /// [`detach`](Tree::detach)ing, this situation will not occur.
-    #[must_use]
-    fn attach<'buffer>(self, buffer: impl Into<Cow<'buffer, str>>) -> Tree<'buffer> {
-        Tree {
-            buffer: buffer.into(),
-            tree: self,
+    /// The buffer must equal the string which was parsed. This is verified by
+    /// comparing its length and a 64-bit hash, which takes time linear in the
+    /// length of the buffer. The check reliably catches accidental mix-ups,
+    /// but is not meant to withstand buffers crafted to collide.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error, which gives back both the buffer and this tree, if
+    /// the buffer differs from the parsed string.
+    pub fn attach<'buffer>(
+        self,
+        buffer: impl Into<Cow<'buffer, str>>,
+    ) -> Result<Tree<'buffer>, MismatchedBuffer<'buffer>> {
+        let buffer = buffer.into();
+        if buffer.len() != self.len || fingerprint(&buffer) != self.fingerprint {
+            return Err(MismatchedBuffer { buffer, tree: self });
         }
+        Ok(self.attach_unchecked(buffer))
+    }
+
+    /// Attach to `buffer`, which is known to be the parsed string.
+    fn attach_unchecked(self, buffer: Cow<'_, str>) -> Tree<'_> {
+        Tree { buffer, tree: self }
     }
 
     /// See [`Tree::negation`].
@@ -55,59 +140,256 @@ impl DetachedTree {
     }
 
     /// See [`Tree::index`].
-    fn index<'tree, 'string, 'index>(
+    fn index<'tree, 'string>(
         &'tree self,
         s: &'string str,
-        path: &'index [&'index str],
+        path: impl IntoIterator<Item = impl AsRef<str>>,
     ) -> Option<Field<'string>>
     where
         'tree: 'string,
     {
-        let mut node_ref = self.tree.root();
-        for &element in path {
-            if let Some(match_) = node_ref
-                .children()
-                .find(|child| &s[child.value().range()] == element)
-            {
-                node_ref = match_;
-            } else {
-                return None;
+        let mut index = NONE;
+        for element in path {
+            index = self.child(s, index, element.as_ref())?;
+        }
+        (index != NONE).then(|| self.field(s, index))
+    }
+
+    /// See [`Tree::get`].
+    fn get<'string>(&'string self, s: &'string str, id: FieldId) -> Option<Field<'string>> {
+        (id.0 < to_u32(self.nodes.len())).then(|| self.field(s, id.0))
+    }
+
+    /// Find where `path` lies relative to the fields of this tree.
+    ///
+    /// Siblings with the same name act as one field, which is a leaf if any
+    /// of them is a leaf, and otherwise has the children of all of them.
+    fn locate(&self, s: &str, path: impl IntoIterator<Item = impl AsRef<str>>) -> Location {
+        let mut indices = vec![NONE];
+        let mut next = Vec::new();
+        for element in path {
+            match self.step(s, &indices, element.as_ref(), &mut next) {
+                Location::Ancestor => {
+                    core::mem::swap(&mut indices, &mut next);
+                    next.clear();
+                }
+                location => return location,
             }
         }
-        Some(Field {
-            buffer: s,
-            node_ref,
-        })
+        Location::Ancestor
+    }
+
+    /// Find where the path to the fields at `indices`, extended by `name`,
+    /// lies relative to the fields of this tree. See [`locate`](Self::locate).
+    ///
+    /// For [`Location::Ancestor`], the indices of the children named `name`
+    /// are appended to `next`.
+    fn step(&self, s: &str, indices: &[u32], name: &str, next: &mut Vec<u32>) -> Location {
+        let len = next.len();
+        for &index in indices {
+            for child in self.children_named(s, index, name) {
+                if !self.has_children(child) {
+                    next.truncate(len);
+                    return Location::Within;
+                }
+                next.push(child);
+            }
+        }
+        if next.len() == len {
+            Location::Outside
+        } else {
+            Location::Ancestor
+        }
     }
 
     /// See [`Tree::walk`].
     fn walk<'string>(&'string self, s: &'string str) -> impl Iterator<Item = Field<'string>> {
-        self.tree.root().descendants().filter_map(|node_ref| {
-            if node_ref.value().is_empty() {
-                None
-            } else {
-                Some(Field {
-                    buffer: s,
-                    node_ref,
-                })
-            }
-        })
+        (0..to_u32(self.nodes.len())).map(|index| self.field(s, index))
     }
 
     /// See [`Tree::top`].
     fn top<'string>(&'string self, s: &'string str) -> impl Iterator<Item = Field<'string>> {
-        self.tree.root().children().map(|node_ref| Field {
-            buffer: s,
-            node_ref,
+        self.children(NONE).map(|index| self.field(s, index))
+    }
+
+    /// Iterate over the indices of the children of the field at `index`, or
+    /// of the top-level fields for [`NONE`].
+    fn children(&self, index: u32) -> impl Iterator<Item = u32> + use<'_> {
+        let (first, end) = if index == NONE {
+            (0, to_u32(self.nodes.len()))
+        } else {
+            (index + 1, self.nodes[index as usize].end)
+        };
+        let mut next = first;
+        core::iter::from_fn(move || {
+            let child = next;
+            (child < end).then(|| {
+                next = self.nodes[child as usize].end;
+                child
+            })
         })
     }
 
+    fn has_children(&self, index: u32) -> bool {
+        self.nodes[index as usize].end > index + 1
+    }
+
+    fn name<'string>(&self, s: &'string str, index: u32) -> &'string str {
+        &s[self.nodes[index as usize].range()]
+    }
+
+    /// Find the first child of the field at `index` named `name`. See
+    /// [`children`](Self::children).
+    fn child(&self, s: &str, index: u32, name: &str) -> Option<u32> {
+        self.children_named(s, index, name).next()
+    }
+
+    /// Iterate over the indices of the children of the field at `index` named
+    /// `name`, in order. See [`children`](Self::children).
+    fn children_named<'t, 'n>(
+        &'t self,
+        s: &'t str,
+        index: u32,
+        name: &'n str,
+    ) -> impl Iterator<Item = u32> + use<'t, 'n> {
+        let sorted = self.wide_children.get(&index).map(|wide| {
+            let sorted = &wide.by_name;
+            let first = sorted.partition_point(|&child| self.name(s, child) < name);
+            sorted[first..]
+                .iter()
+                .copied()
+                .take_while(move |&child| self.name(s, child) == name)
+        });
+        let linear = sorted.is_none().then(|| {
+            self.children(index)
+                .filter(move |&child| self.name(s, child) == name)
+        });
+        sorted
+            .into_iter()
+            .flatten()
+            .chain(linear.into_iter().flatten())
+    }
+
+    /// Sort the children of every field with at least [`WIDE`] children by
+    /// name.
+    fn sort_children(&mut self, s: &str) {
+        let parents = core::iter::once(NONE).chain(0..to_u32(self.nodes.len()));
+        for parent in parents {
+            if self.children(parent).nth(WIDE - 1).is_none() {
+                continue;
+            }
+            let in_order: Box<[_]> = self.children(parent).collect();
+            let mut by_name = in_order.clone();
+            // Stable, so that children with the same name stay in order.
+            by_name.sort_by_key(|&child| self.name(s, child));
+            self.wide_children
+                .insert(parent, WideChildren { by_name, in_order });
+        }
+    }
+
+    /// Find the child of the field at `parent`, or the top-level field for
+    /// [`NONE`], whose subtree contains the field at `index`.
+    fn child_towards(&self, parent: u32, index: u32) -> u32 {
+        if let Some(wide) = self.wide_children.get(&parent) {
+            let after = wide.in_order.partition_point(|&child| child <= index);
+            return wide.in_order[after - 1];
+        }
+        self.children(parent)
+            .find(|&child| index < self.nodes[child as usize].end)
+            .expect("the field is a descendant of the parent")
+    }
+
+    fn field<'string>(&'string self, s: &'string str, index: u32) -> Field<'string> {
+        Field {
+            buffer: s,
+            tree: self,
+            index,
+        }
+    }
+
+    /// See [`Tree::traverse`].
+    fn traverse<'string>(&'string self, s: &'string str) -> Traverse<'string> {
+        Traverse {
+            buffer: s,
+            tree: self,
+            next: 0,
+            end: to_u32(self.nodes.len()),
+            open: NONE,
+            root: NONE,
+        }
+    }
+
     /// See [`Tree::leaves`].
     fn leaves<'string>(&'string self, s: &'string str) -> impl Iterator<Item = Field<'string>> {
-        self.walk(s).filter(|field| !field.has_children())
+        (0..to_u32(self.nodes.len()))
+            .filter(|&index| !self.has_children(index))
+            .map(|index| self.field(s, index))
+    }
+}
+
+/// Iterates over the edges of a range of fields in a [`DetachedTree`].
+///
+/// Since the fields are stored in pre-order, every field is entered in order,
+/// and the open fields are left by following their parents once the next
+/// field lies outside of their subtrees.
+struct Traverse<'p> {
+    buffer: &'p str,
+    tree: &'p DetachedTree,
+    /// The index of the next field to enter.
+    next: u32,
+    /// The index following the last field to enter.
+    end: u32,
+    /// The most recently entered field which has not been left, or [`NONE`].
+    open: u32,
+    /// The parent of the traversed fields, which is not left.
+    root: u32,
+}
+
+impl<'p> Iterator for Traverse<'p> {
+    type Item = Edge<'p>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.open != NONE && self.tree.nodes[self.open as usize].end <= self.next {
+            let field = self.tree.field(self.buffer, self.open);
+            self.open = self.tree.nodes[self.open as usize].parent;
+            if self.open == self.root {
+                self.open = NONE;
+            }
+            return Some(Edge::Leave(field));
+        }
+        if self.next < self.end {
+            self.open = self.next;
+            self.next += 1;
+            return Some(Edge::Enter(self.tree.field(self.buffer, self.open)));
+        }
+        None
     }
 }
 
+/// Where a path lies relative to the fields of a [`DetachedTree`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Location {
+    /// The path, or one of its prefixes, is a leaf.
+    Within,
+    /// The path ends at a field which has children.
+    Ancestor,
+    /// The path leaves the tree without passing through a leaf.
+    Outside,
+}
+
+/// Whether a path is selected by a [`Tree`].
+///
+/// Returned by [`Tree::decide`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Decision {
+    /// The path and everything below it is selected.
+    Include,
+    /// The path and everything below it is filtered out.
+    Exclude,
+    /// Some of what is below the path is selected. Decide for each child.
+    Partial,
+}
+
 /// Contains fields parsed from a filtering string.
 ///
 /// See usage examples in the [crate documentation](crate).
@@ -122,7 +404,7 @@ impl<'buffer> Tree<'buffer> {
     ///
     /// Should rarely be needed. Can be re-[`attach`](DetachedTree::attach)ed.
     #[must_use]
-    fn detach(self) -> (Cow<'buffer, str>, DetachedTree) {
+    pub fn detach(self) -> (Cow<'buffer, str>, DetachedTree) {
         (self.buffer, self.tree)
     }
 
@@ -138,7 +420,7 @@ impl<'buffer> Tree<'buffer> {
         fn inner(cow: Cow<str>) -> Result<Tree, Unparsable> {
             let detached = Tree::parse_detached(&cow);
             match detached {
-                Ok(detached) => Ok(detached.attach(cow)),
+                Ok(detached) => Ok(detached.attach_unchecked(cow)),
                 Err(Unparsable { error, buffer }) => {
                     drop(buffer);
                     Err(Unparsable { error, buffer: cow })
@@ -167,20 +449,25 @@ impl<'buffer> Tree<'buffer> {
     }
 }
 
+impl Tree<'static> {
+    /// Create a tree from a filter string written by this crate.
+    pub(crate) fn from_written(buffer: String) -> Self {
+        Tree::parse(buffer).expect("written filters are valid")
+    }
+}
+
 impl Tree<'_> {
     /// Attempt to parse `s` into a tree of [`Field`]s.
     ///
     /// Returns a detached [`DetachedTree`] which is not linked to the parsed
-    /// string. This can cause trouble if later
-    /// [`attach`](DetachedTree::attach)ing to a different string. See that
-    /// method's documentation for more information. In most cases, prefer
-    /// [`parse`](Self::parse).
+    /// string. It can only be [`attach`](DetachedTree::attach)ed to a string
+    /// equal to `s`. In most cases, prefer [`parse`](Self::parse).
     ///
     /// # Errors
     ///
     /// Returns an error if `s` does not match the expected format.
     #[allow(clippy::missing_panics_doc)] // panics should be impossible
-    fn parse_detached(s: &str) -> Result<DetachedTree, Unparsable<'_>> {
+    pub fn parse_detached(s: &str) -> Result<DetachedTree, Unparsable<'_>> {
         let fields = match parser::Fields::try_from(s) {
             Ok(fields) => fields,
             Err(error) => {
@@ -190,54 +477,58 @@ impl Tree<'_> {
                 });
             }
         };
-        // The root node should not be exposed - does not represent a Field.
-        // "" is not a valid field name, so will never appear further down in the tree.
-        let mut tree = ego_tree::Tree::new(&s[0..0]);
-        let mut stack: Vec<_> = fields
-            .fields_struct
-            .0
-            .0
-            .into_iter()
-            .filter_map(|field| match field {
+        if u32::try_from(s.len()).is_err() {
+            return Err(Unparsable {
+                error: parser::Error::new("input is 4 GiB or longer".to_string(), 0),
+                buffer: Cow::Borrowed(s),
+            });
+        }
+        let mut nodes: Vec<Node> = Vec::new();
+        // The parents of the fields in each iterator, which yield the
+        // children in order, so that the nodes are pushed in pre-order.
+        let mut stack = vec![(NONE, fields.fields_struct.field_items.0.into_iter())];
+        while let Some((parent, children)) = stack.last_mut() {
+            let parent = *parent;
+            let Some(child) = children.next() else {
+                stack.pop();
+                if parent != NONE {
+                    nodes[parent as usize].end = to_u32(nodes.len());
+                }
+                continue;
+            };
+            let index = to_u32(nodes.len());
+            let (field_name, children) = match child {
                 parser::Field::FieldsSubstruct(parser::FieldsSubstruct {
                     field_name,
                     fields_struct,
-                }) => {
-                    let mut parent = tree.root_mut();
-                    let current = parent.append(field_name.0);
-                    let current_id = current.id();
-                    Some((current_id, fields_struct.0.0))
-                }
-                parser::Field::FieldName(field_name) => {
-                    tree.root_mut().append(field_name.0);
-                    None
-                }
-            })
-            .collect();
-
-        while let Some((v_id, v_children)) = stack.pop() {
-            let mut v = tree.get_mut(v_id).expect("all node ids are valid");
-            for w in v_children {
-                match w {
-                    parser::Field::FieldsSubstruct(parser::FieldsSubstruct {
-                        field_name,
-                        fields_struct,
-                    }) => {
-                        let id = v.append(field_name.0).id();
-                        stack.push((id, fields_struct.0.0));
-                    }
-                    parser::Field::FieldName(field_name) => {
-                        v.append(field_name.0);
-                    }
-                }
+                }) => (field_name, Some(fields_struct.field_items.0)),
+                parser::Field::FieldName(field_name) => (field_name, None),
+            };
+            nodes.push(Node {
+                start: to_u32(field_name.span.start),
+                len: to_u32(field_name.span.len()),
+                end: index + 1,
+                parent,
+                depth: if parent == NONE {
+                    0
+                } else {
+                    nodes[parent as usize].depth + 1
+                },
+            });
+            if let Some(children) = children {
+                stack.push((index, children.into_iter()));
             }
         }
 
-        let tree = tree.map(|field_name| {
-            StrRange::new(s, field_name).expect("all field names are slices of the buffer s")
-        });
-        let negation = fields.negation;
-        Ok(DetachedTree { tree, negation })
+        let mut detached = DetachedTree {
+            nodes,
+            negation: fields.negation,
+            wide_children: BTreeMap::new(),
+            len: s.len(),
+            fingerprint: fingerprint(s),
+        };
+        detached.sort_children(s);
+        Ok(detached)
     }
 
     /// Whether these fields should represent a denylist rather than an
@@ -249,6 +540,10 @@ impl Tree<'_> {
 
     /// Look up a field by its path.
     ///
+    /// The path can be anything which iterates over field names, such as
+    /// `&[&str]` or a [`FieldPath`](crate::FieldPath). The empty path does not
+    /// lead to a field.
+    ///
     /// # Example
     ///
     /// ```
@@ -257,10 +552,38 @@ impl Tree<'_> {
     /// assert_eq!(field.name(), "b");
     /// ```
     #[must_use]
-    pub fn index<'index>(&self, path: &'index [&'index str]) -> Option<Field<'_>> {
+    pub fn index(&self, path: impl IntoIterator<Item = impl AsRef<str>>) -> Option<Field<'_>> {
         self.tree.index(&self.buffer, path)
     }
 
+    /// Look up a field by an id obtained from [`Field::id`].
+    ///
+    /// Ids are only meaningful for the tree whose fields produced them, and
+    /// are invalidated when the tree is changed, e.g., by
+    /// [`insert_path`](Self::insert_path). Returns `None` if the id is out of
+    /// range for this tree. An id from another tree, or from before a change,
+    /// may still be in range, in which case an unspecified field is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    ///
+    /// let tree =
+    ///     z157::Tree::parse("(name,address(city))").unwrap();
+    /// let columns: HashMap<_, _> = tree
+    ///     .leaves()
+    ///     .map(|field| (field.id(), field.path().join("_")))
+    ///     .collect();
+    /// let city = tree.index(&["address", "city"]).unwrap();
+    /// assert_eq!(columns[&city.id()], "address_city");
+    /// assert_eq!(tree.get(city.id()).unwrap().name(), "city");
+    /// ```
+    #[must_use]
+    pub fn get(&self, id: FieldId) -> Option<Field<'_>> {
+        self.tree.get(&self.buffer, id)
+    }
+
     /// Iterate over all fields.
     pub fn walk(&self) -> impl Iterator<Item = Field<'_>> {
         self.tree.walk(&self.buffer)
@@ -289,20 +612,322 @@ impl Tree<'_> {
     pub fn leaves(&self) -> impl Iterator<Item = Field<'_>> {
         self.tree.leaves(&self.buffer)
     }
+
+    /// Iterate over the edges of the tree in depth-first order.
+    ///
+    /// Every field is [entered](Edge::Enter) before its children, and
+    /// [left](Edge::Leave) after them, which allows keeping track of where
+    /// each subtree ends.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use z157::Edge;
+    ///
+    /// let tree = z157::Tree::parse("(a(b,c),d)").unwrap();
+    /// let mut nested = String::new();
+    /// for edge in tree.traverse() {
+    ///     match edge {
+    ///         Edge::Enter(field) => {
+    ///             nested.push_str(field.name());
+    ///             nested.push('{');
+    ///         }
+    ///         Edge::Leave(_) => nested.push('}'),
+    ///     }
+    /// }
+    /// assert_eq!(nested, "a{b{}c{}}d{}");
+    /// ```
+    pub fn traverse(&self) -> impl Iterator<Item = Edge<'_>> {
+        self.tree.traverse(&self.buffer)
+    }
+
+    /// Iterate over all fields in the same order as [`walk`](Self::walk),
+    /// along with their [depth](Field::depth).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let tree = z157::Tree::parse("(a(b),c)").unwrap();
+    /// let fields: Vec<_> = tree
+    ///     .walk_with_depth()
+    ///     .map(|(depth, field)| (depth, field.name()))
+    ///     .collect();
+    /// assert_eq!(fields, [(0, "a"), (1, "b"), (0, "c")]);
+    /// ```
+    pub fn walk_with_depth(&self) -> impl Iterator<Item = (usize, Field<'_>)> {
+        let mut depth = 0;
+        self.traverse().filter_map(move |edge| match edge {
+            Edge::Enter(field) => {
+                depth += 1;
+                Some((depth - 1, field))
+            }
+            Edge::Leave(_) => {
+                depth -= 1;
+                None
+            }
+        })
+    }
+
+    /// Iterate over all fields level by level, starting with the top-level
+    /// fields.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let tree = z157::Tree::parse("(a(b(c)),d(e))").unwrap();
+    /// let fields: Vec<_> = tree
+    ///     .breadth_first()
+    ///     .map(|field| field.name())
+    ///     .collect();
+    /// assert_eq!(fields, ["a", "d", "b", "e", "c"]);
+    /// ```
+    pub fn breadth_first(&self) -> impl Iterator<Item = Field<'_>> {
+        let mut queue: VecDeque<_> = self.top().collect();
+        core::iter::from_fn(move || {
+            let field = queue.pop_front()?;
+            queue.extend(field.children());
+            Some(field)
+        })
+    }
+
+    /// Iterate over all fields, where every field comes after its children.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let tree = z157::Tree::parse("(a(b(c)),d(e))").unwrap();
+    /// let fields: Vec<_> = tree
+    ///     .post_order()
+    ///     .map(|field| field.name())
+    ///     .collect();
+    /// assert_eq!(fields, ["c", "b", "a", "e", "d"]);
+    /// ```
+    pub fn post_order(&self) -> impl Iterator<Item = Field<'_>> {
+        self.traverse().filter_map(|edge| match edge {
+            Edge::Enter(_) => None,
+            Edge::Leave(field) => Some(field),
+        })
+    }
+
+    /// Whether a response filtered by this tree contains everything that
+    /// `other` asks for.
+    ///
+    /// A leaf selects the entire subtree below it, and a
+    /// [negation](Self::negation) turns a tree into a denylist. Since the
+    /// shape of the filtered document is unknown, an allowlist never covers a
+    /// denylist.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use z157::Tree;
+    ///
+    /// let cached = Tree::parse("(name,address)").unwrap();
+    /// let requested =
+    ///     Tree::parse("(name,address(city))").unwrap();
+    /// assert!(cached.covers(&requested));
+    /// assert!(!requested.covers(&cached));
+    /// ```
+    #[must_use]
+    pub fn covers(&self, other: &Tree<'_>) -> bool {
+        match (self.negation(), other.negation()) {
+            // Everything `other` selects lies within something `self` selects.
+            (false, false) => other
+                .leaves()
+                .all(|leaf| self.locate(leaf.path_iter()) == Location::Within),
+            // Nothing `other` selects overlaps with something `self` excludes.
+            (true, false) => other
+                .leaves()
+                .all(|leaf| self.locate(leaf.path_iter()) == Location::Outside),
+            // `other` selects fields which `self` cannot know about.
+            (false, true) => false,
+            // Everything `self` excludes lies within something `other` excludes.
+            (true, true) => self
+                .leaves()
+                .all(|leaf| other.locate(leaf.path_iter()) == Location::Within),
+        }
+    }
+
+    /// Decide whether the value found at `path` should be part of a filtered
+    /// response.
+    ///
+    /// A leaf selects the entire subtree below it, and a
+    /// [negation](Self::negation) turns the tree into a denylist. The empty
+    /// path refers to the whole document, which is always
+    /// [`Partial`](Decision::Partial). As with [`index`](Self::index), the path
+    /// can be anything which iterates over field names.
+    ///
+    /// Siblings with the same name are combined, so `(a(b),a(c))` decides the
+    /// same as `(a(b,c))`, and `(a(b),a)` the same as `(a)`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use z157::Decision;
+    /// use z157::Tree;
+    ///
+    /// let tree = Tree::parse("(name,address(city))").unwrap();
+    /// assert_eq!(
+    ///     tree.decide(&["name", "first"]),
+    ///     Decision::Include
+    /// );
+    /// assert_eq!(
+    ///     tree.decide(&["address"]),
+    ///     Decision::Partial
+    /// );
+    /// assert_eq!(
+    ///     tree.decide(&["address", "zip"]),
+    ///     Decision::Exclude
+    /// );
+    ///
+    /// let tree =
+    ///     Tree::parse("!(name,address(city))").unwrap();
+    /// assert_eq!(
+    ///     tree.decide(&["name", "first"]),
+    ///     Decision::Exclude
+    /// );
+    /// assert_eq!(
+    ///     tree.decide(&["address"]),
+    ///     Decision::Partial
+    /// );
+    /// assert_eq!(
+    ///     tree.decide(&["address", "zip"]),
+    ///     Decision::Include
+    /// );
+    /// ```
+    #[must_use]
+    pub fn decide(&self, path: impl IntoIterator<Item = impl AsRef<str>>) -> Decision {
+        match (self.locate(path), self.negation()) {
+            (Location::Within, false) | (Location::Outside, true) => Decision::Include,
+            (Location::Within, true) | (Location::Outside, false) => Decision::Exclude,
+            (Location::Ancestor, _) => Decision::Partial,
+        }
+    }
+
+    /// Create a [`Matcher`] for deciding on keys one at a time while walking
+    /// a nested document.
+    #[must_use]
+    pub fn matcher(&self) -> Matcher<'_> {
+        Matcher::new(self)
+    }
+
+    /// Compile this tree into an [`Automaton`] for high-throughput matching.
+    #[must_use]
+    pub fn compile(&self) -> Automaton {
+        Automaton::new(self)
+    }
+
+    fn locate(&self, path: impl IntoIterator<Item = impl AsRef<str>>) -> Location {
+        self.tree.locate(&self.buffer, path)
+    }
+
+    /// Iterate over the top-level fields named `name`, in order.
+    pub(crate) fn top_named(&self, name: &str) -> impl Iterator<Item = Field<'_>> {
+        let (buffer, tree) = (&*self.buffer, &self.tree);
+        tree.children_named(buffer, NONE, name)
+            .map(move |index| tree.field(buffer, index))
+    }
+}
+
+/// Writes the filter string which these fields were parsed from or, after
+/// changes, the equivalent filter string.
+///
+/// Since a tree always has at least one field, the result can always be parsed
+/// again.
+impl fmt::Display for Tree<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.buffer)
+    }
+}
+
+/// An opaque handle to a [`Field`], which does not borrow the [`Tree`].
+///
+/// Can be stored along with the tree, e.g., as a key for per-field metadata,
+/// and turned back into a `Field` with [`Tree::get`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FieldId(u32);
+
+/// An edge in a depth-first traversal of fields.
+///
+/// Returned by [`Tree::traverse`] and [`Field::traverse`].
+#[derive(Clone)]
+pub enum Edge<'p> {
+    /// The traversal enters a field, before any of its children.
+    Enter(Field<'p>),
+    /// The traversal leaves a field, after all of its children.
+    Leave(Field<'p>),
 }
 
 /// One node in the tree of fields.
 #[derive(Clone)]
 pub struct Field<'p> {
     buffer: &'p str,
-    node_ref: ego_tree::NodeRef<'p, StrRange>,
+    tree: &'p DetachedTree,
+    index: u32,
 }
 
 impl<'p> Field<'p> {
     /// Get the field name.
     #[must_use]
     pub fn name(&self) -> &'p str {
-        &self.buffer[self.node_ref.value().range()]
+        self.tree.name(self.buffer, self.index)
+    }
+
+    /// The location of the field name in the filter string, i.e., the buffer
+    /// of the [`Tree`].
+    ///
+    /// Useful for pointing at the exact spot of a problem in the filter
+    /// string, e.g., when a client asks for an unknown field.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let tree =
+    ///     z157::Tree::parse("(name,address(city))").unwrap();
+    /// let city = tree.index(&["address", "city"]).unwrap();
+    /// assert_eq!(city.span(), 14..18);
+    /// ```
+    #[must_use]
+    pub fn span(&self) -> Range<usize> {
+        self.node().range()
+    }
+
+    /// The location of this field in the filter string, including its
+    /// children and their surrounding parentheses.
+    ///
+    /// For fields without children, this is the same as
+    /// [`span`](Field::span).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let s = "(name,address(city,geo(lat)),age)";
+    /// let tree = z157::Tree::parse(s).unwrap();
+    /// let address = tree.index(&["address"]).unwrap();
+    /// assert_eq!(
+    ///     &s[address.substruct_span()],
+    ///     "address(city,geo(lat))"
+    /// );
+    /// ```
+    #[must_use]
+    pub fn substruct_span(&self) -> Range<usize> {
+        // The closing parentheses directly follow the name of the last
+        // descendant, one for each level between it and this field.
+        let last = self.node().end - 1;
+        let mut levels = 0;
+        let mut ancestor = last;
+        while ancestor != self.index {
+            ancestor = self.tree.nodes[ancestor as usize].parent;
+            levels += 1;
+        }
+        self.span().start..self.tree.nodes[last as usize].range().end + levels
+    }
+
+    /// Get a handle to this field which can be stored independently of the
+    /// [`Tree`]. See [`Tree::get`].
+    #[must_use]
+    pub fn id(&self) -> FieldId {
+        FieldId(self.index)
     }
 
     /// Return the parent of this field if possible.
@@ -319,31 +944,62 @@ impl<'p> Field<'p> {
     /// ```
     #[must_use]
     pub fn parent(&self) -> Option<Field<'p>> {
-        self.node_ref
-            .parent()
-            // Field names are at least 1 character long, so only the root note (which is not an
-            // actual field) is empty
-            .filter(|parent| !parent.value().is_empty())
-            .map(|node_ref| Field {
-                buffer: self.buffer,
-                node_ref,
-            })
+        let parent = self.node().parent;
+        (parent != NONE).then(|| self.tree.field(self.buffer, parent))
     }
 
     /// Iterate over this field's children (one level).
     pub fn children(&self) -> impl Iterator<Item = Field<'p>> + use<'p> {
-        self.node_ref.children().map(|node_ref| Field {
-            buffer: self.buffer,
-            node_ref,
-        })
+        let (buffer, tree) = (self.buffer, self.tree);
+        tree.children(self.index)
+            .map(move |index| tree.field(buffer, index))
+    }
+
+    /// Look up a child of this field by name.
+    ///
+    /// If several children have the same name, the first one is returned.
+    /// Fields with many children are indexed when parsed, so that this lookup
+    /// does not need to compare against every child.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let tree = z157::Tree::parse("(a(b,c))").unwrap();
+    /// let a = tree.index(&["a"]).unwrap();
+    /// assert_eq!(a.child("c").unwrap().name(), "c");
+    /// assert!(a.child("d").is_none());
+    /// ```
+    #[must_use]
+    pub fn child(&self, name: &str) -> Option<Field<'p>> {
+        self.tree
+            .child(self.buffer, self.index, name)
+            .map(|index| self.tree.field(self.buffer, index))
+    }
+
+    /// Iterate over the children of this field named `name`, in order.
+    pub(crate) fn children_named(&self, name: &str) -> impl Iterator<Item = Field<'p>> {
+        let (buffer, tree) = (self.buffer, self.tree);
+        tree.children_named(buffer, self.index, name)
+            .map(move |index| tree.field(buffer, index))
     }
 
     /// Iterate over all descendants of this field (including self).
     pub fn walk(&self) -> impl Iterator<Item = Field<'p>> + 'p + use<'p> {
-        self.node_ref.descendants().map(|node_ref| Field {
+        let (buffer, tree) = (self.buffer, self.tree);
+        (self.index..self.node().end).map(move |index| tree.field(buffer, index))
+    }
+
+    /// Iterate over the edges of the subtree of this field (including self) in
+    /// depth-first order. See [`Tree::traverse`].
+    pub fn traverse(&self) -> impl Iterator<Item = Edge<'p>> + use<'p> {
+        Traverse {
             buffer: self.buffer,
-            node_ref,
-        })
+            tree: self.tree,
+            next: self.index,
+            end: self.node().end,
+            open: NONE,
+            root: self.node().parent,
+        }
     }
 
     /// Return the path for this node.
@@ -357,23 +1013,115 @@ impl<'p> Field<'p> {
     /// ```
     #[must_use]
     pub fn path(&self) -> Vec<&'p str> {
-        let mut path_list = vec![&self.buffer[self.node_ref.value().range()]];
-        let mut current = self.clone();
-        while let Some(parent) = current.parent() {
-            path_list.push(&self.buffer[parent.node_ref.value().range()]);
-            current = parent;
+        self.path_iter().collect()
+    }
+
+    /// Iterate over the names in the path for this node, from the top level
+    /// down to this field, without allocating.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let tree = z157::Tree::parse("(a(b(c)))").unwrap();
+    /// let c = tree.index(&["a", "b", "c"]).unwrap();
+    /// assert!(c.path_iter().eq(["a", "b", "c"]));
+    /// ```
+    #[must_use]
+    pub fn path_iter(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = &'p str> + ExactSizeIterator + use<'p> {
+        PathIter {
+            buffer: self.buffer,
+            tree: self.tree,
+            target: self.index,
+            front: NONE,
+            back: self.index,
+            len: self.depth() + 1,
         }
-        path_list.reverse();
-        path_list
+    }
+
+    /// Iterate over the parents of this field, from its parent up to the top
+    /// level.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let tree = z157::Tree::parse("(a(b(c)))").unwrap();
+    /// let c = tree.index(&["a", "b", "c"]).unwrap();
+    /// let ancestors: Vec<_> =
+    ///     c.ancestors().map(|field| field.name()).collect();
+    /// assert_eq!(ancestors, ["b", "a"]);
+    /// ```
+    pub fn ancestors(&self) -> impl Iterator<Item = Field<'p>> + use<'p> {
+        core::iter::successors(self.parent(), Field::parent)
+    }
+
+    /// The number of parents of this field. Top-level fields have depth 0.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.node().depth as usize
     }
 
     /// Return true if this field has children.
     #[must_use]
     pub fn has_children(&self) -> bool {
-        self.node_ref.has_children()
+        self.tree.has_children(self.index)
+    }
+
+    fn node(&self) -> Node {
+        self.tree.nodes[self.index as usize]
+    }
+}
+
+/// Iterates over the names in the path of a field. See [`Field::path_iter`].
+///
+/// The front descends from the top level towards the field, and the back
+/// ascends from the field through its parents, so that every name is found in
+/// a single step.
+struct PathIter<'p> {
+    buffer: &'p str,
+    tree: &'p DetachedTree,
+    /// The field whose path is iterated.
+    target: u32,
+    /// The most recently yielded field from the front, or [`NONE`].
+    front: u32,
+    /// The next field to yield from the back.
+    back: u32,
+    /// The number of names which have not been yielded.
+    len: usize,
+}
+
+impl<'p> Iterator for PathIter<'p> {
+    type Item = &'p str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        self.front = self.tree.child_towards(self.front, self.target);
+        Some(self.tree.name(self.buffer, self.front))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
     }
 }
 
+impl DoubleEndedIterator for PathIter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        let index = self.back;
+        self.back = self.tree.nodes[index as usize].parent;
+        Some(self.tree.name(self.buffer, index))
+    }
+}
+
+impl ExactSizeIterator for PathIter<'_> {}
+
 /// Returned when parsing of a string into a [`Tree`] fails.
 #[derive(Debug)]
 pub struct Unparsable<'buffer> {
@@ -387,8 +1135,9 @@ impl fmt::Display for Unparsable<'_> {
     }
 }
 
-impl std::error::Error for Unparsable<'_> {}
+impl core::error::Error for Unparsable<'_> {}
 
+#[allow(dead_code)] // not exported
 #[derive(Debug)]
 pub struct UnparsableRef {
     error: parser::Error,
@@ -400,12 +1149,79 @@ impl fmt::Display for UnparsableRef {
     }
 }
 
-impl std::error::Error for UnparsableRef {}
+impl core::error::Error for UnparsableRef {}
+
+/// Returned when [attaching](DetachedTree::attach) a [`DetachedTree`] to a
+/// buffer which differs from the parsed string.
+#[derive(Debug)]
+pub struct MismatchedBuffer<'buffer> {
+    /// The buffer which was passed to [`attach`](DetachedTree::attach).
+    pub buffer: Cow<'buffer, str>,
+    /// The tree which could not be attached, so that it can be attached to
+    /// the right buffer instead.
+    pub tree: DetachedTree,
+}
+
+impl fmt::Display for MismatchedBuffer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("buffer differs from the string the tree was parsed from")
+    }
+}
+
+impl core::error::Error for MismatchedBuffer<'_> {}
+
+/// Returned when a string is not a valid field name.
+///
+/// Field names are non-empty and consist of ASCII letters, digits, `-` and
+/// `_`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidName {
+    pub name: String,
+}
+
+impl fmt::Display for InvalidName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid field name: {:?}", self.name)
+    }
+}
+
+impl core::error::Error for InvalidName {}
+
+/// Returned when a [`Tree`] would be left without any fields.
+///
+/// A filter string has at least one field, so a tree without fields could not
+/// be written as one. Whether no fields should select nothing, as for an
+/// allowlist, or everything, as for a denylist, is up to the caller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NoFields;
+
+impl fmt::Display for NoFields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a tree needs at least one field")
+    }
+}
+
+impl core::error::Error for NoFields {}
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_cases::INVALID_FILTERS;
+    use crate::test_cases::VALID_FILTERS;
+
+    #[test]
+    fn test_filters() {
+        for &s in VALID_FILTERS {
+            assert!(Tree::parse(s).is_ok(), "{s:?}");
+        }
+        for &s in INVALID_FILTERS {
+            assert!(Tree::parse(s).is_err(), "{s:?}");
+        }
+    }
+
+    // Exercise the borrowed slices used before paths became generic.
     #[test]
+    #[allow(clippy::needless_borrows_for_generic_args)]
     fn test_parent() {
         let tree = Tree::parse("(a(b))".to_string()).unwrap();
         let b = tree.index(&["a", "b"]).unwrap();
@@ -414,7 +1230,7 @@ mod tests {
     }
 
     #[test]
-    fn test_ego_tree_root_is_excluded_from_walk() {
+    fn test_root_is_excluded_from_walk() {
         let tree = Tree::parse("(a)".to_string()).unwrap();
         let mut fields: Vec<_> = tree.walk().map(|f| f.name()).collect();
         fields.sort_unstable();
@@ -446,4 +1262,244 @@ mod tests {
         leaves.sort_unstable();
         assert_eq!(leaves, ["c", "d", "e"]);
     }
+
+    #[test]
+    fn test_covers_allowlists() {
+        let cached = Tree::parse("(name,address(city,zip))").unwrap();
+        assert!(cached.covers(&Tree::parse("(name)").unwrap()));
+        assert!(cached.covers(&Tree::parse("(address(city))").unwrap()));
+        assert!(cached.covers(&cached));
+        assert!(!cached.covers(&Tree::parse("(address)").unwrap()));
+        assert!(!cached.covers(&Tree::parse("(age)").unwrap()));
+
+        let cached = Tree::parse("(address)").unwrap();
+        assert!(cached.covers(&Tree::parse("(address(city(code)))").unwrap()));
+
+        let cached = Tree::parse("(a(b),a(c))").unwrap();
+        assert!(cached.covers(&cached));
+        assert!(cached.covers(&Tree::parse("(a(c))").unwrap()));
+        assert!(cached.covers(&Tree::parse("(a(b,c))").unwrap()));
+        assert!(!cached.covers(&Tree::parse("(a(d))").unwrap()));
+        assert!(Tree::parse("(a(b,c))").unwrap().covers(&cached));
+        let denied = Tree::parse("!(a(b),a(c))").unwrap();
+        assert!(Tree::parse("!(a(c))").unwrap().covers(&denied));
+        assert!(!denied.covers(&Tree::parse("!(a(c))").unwrap()));
+    }
+
+    #[test]
+    fn test_covers_denylists() {
+        let cached = Tree::parse("!(address(city))").unwrap();
+        assert!(cached.covers(&Tree::parse("(name,address(zip))").unwrap()));
+        assert!(!cached.covers(&Tree::parse("(address)").unwrap()));
+        assert!(!cached.covers(&Tree::parse("(address(city(code)))").unwrap()));
+        assert!(cached.covers(&Tree::parse("!(address)").unwrap()));
+        assert!(cached.covers(&Tree::parse("!(address(city),name)").unwrap()));
+        assert!(!cached.covers(&Tree::parse("!(name)").unwrap()));
+
+        let cached = Tree::parse("(name,address)").unwrap();
+        assert!(!cached.covers(&Tree::parse("!(name)").unwrap()));
+    }
+
+    // Exercise the borrowed slices used before paths became generic.
+    #[test]
+    #[allow(clippy::needless_borrows_for_generic_args)]
+    fn test_decide() {
+        let tree = Tree::parse("(a(b(c),d),e)").unwrap();
+        assert_eq!(tree.decide(&[] as &[&str]), Decision::Partial);
+        assert_eq!(tree.decide(&["a"]), Decision::Partial);
+        assert_eq!(tree.decide(&["a", "b"]), Decision::Partial);
+        assert_eq!(tree.decide(&["a", "b", "c"]), Decision::Include);
+        assert_eq!(tree.decide(&["a", "b", "c", "x"]), Decision::Include);
+        assert_eq!(tree.decide(&["a", "d", "x"]), Decision::Include);
+        assert_eq!(tree.decide(&["a", "x"]), Decision::Exclude);
+        assert_eq!(tree.decide(&["x"]), Decision::Exclude);
+
+        let tree = Tree::parse("!(a(b),c)").unwrap();
+        assert_eq!(tree.decide(&[] as &[&str]), Decision::Partial);
+        assert_eq!(tree.decide(&["a"]), Decision::Partial);
+        assert_eq!(tree.decide(&["a", "b"]), Decision::Exclude);
+        assert_eq!(tree.decide(&["a", "x"]), Decision::Include);
+        assert_eq!(tree.decide(&["c", "x"]), Decision::Exclude);
+        assert_eq!(tree.decide(&["x"]), Decision::Include);
+    }
+
+    #[test]
+    fn test_decide_repeated_siblings() {
+        let tree = Tree::parse("(a(b),a(c(d)),e)").unwrap();
+        assert_eq!(tree.decide(["a"]), Decision::Partial);
+        assert_eq!(tree.decide(["a", "b"]), Decision::Include);
+        assert_eq!(tree.decide(["a", "c"]), Decision::Partial);
+        assert_eq!(tree.decide(["a", "c", "d"]), Decision::Include);
+        assert_eq!(tree.decide(["a", "x"]), Decision::Exclude);
+
+        let tree = Tree::parse("(a(b),a)").unwrap();
+        assert_eq!(tree.decide(["a"]), Decision::Include);
+        assert_eq!(tree.decide(["a", "c"]), Decision::Include);
+
+        let tree = Tree::parse("!(a(b),a(c))").unwrap();
+        assert_eq!(tree.decide(["a", "b"]), Decision::Exclude);
+        assert_eq!(tree.decide(["a", "c"]), Decision::Exclude);
+        assert_eq!(tree.decide(["a", "d"]), Decision::Include);
+    }
+
+    #[test]
+    fn test_ancestors_and_depth() {
+        let tree = Tree::parse("(a(b(c)),d)").unwrap();
+        let c = tree.index(["a", "b", "c"]).unwrap();
+        assert_eq!(c.depth(), 2);
+        assert_eq!(tree.index(["d"]).unwrap().depth(), 0);
+        let ancestors: Vec<_> = c.ancestors().map(|f| f.name()).collect();
+        assert_eq!(ancestors, ["b", "a"]);
+        assert_eq!(c.path_iter().len(), 3);
+        assert!(c.path_iter().rev().eq(["c", "b", "a"]));
+        assert_eq!(c.path(), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_path_iter() {
+        let names: Vec<_> = (0..20).map(|i| format!("f{i}")).collect();
+        let wide = names.join(",");
+        let filter = format!("({wide},a({wide},b(c({wide},d)),e),{wide})");
+        let tree = Tree::parse(filter).unwrap();
+        for field in tree.walk() {
+            let mut expected: Vec<_> = field.ancestors().map(|f| f.name()).collect();
+            expected.reverse();
+            expected.push(field.name());
+            assert_eq!(field.depth(), expected.len() - 1);
+            assert!(field.path_iter().eq(expected.iter().copied()));
+            assert!(field.path_iter().rev().eq(expected.iter().rev().copied()));
+        }
+
+        let d = tree.index(["a", "b", "c", "d"]).unwrap();
+        let mut path = d.path_iter();
+        assert_eq!(path.next(), Some("a"));
+        assert_eq!(path.next_back(), Some("d"));
+        assert_eq!(path.len(), 2);
+        assert_eq!(path.next_back(), Some("c"));
+        assert_eq!(path.next(), Some("b"));
+        assert_eq!(path.next(), None);
+        assert_eq!(path.next_back(), None);
+
+        let deep = format!("({}x{})", "x(".repeat(300), ")".repeat(300));
+        let tree = Tree::parse(deep).unwrap();
+        let leaf = tree.leaves().next().unwrap();
+        assert_eq!(leaf.depth(), 300);
+        assert_eq!(leaf.path_iter().len(), 301);
+    }
+
+    #[test]
+    fn test_get() {
+        let tree = Tree::parse("(a(b),c)").unwrap();
+        for field in tree.walk() {
+            assert_eq!(tree.get(field.id()).unwrap().path(), field.path());
+        }
+        assert!(tree.index([""; 0]).is_none());
+
+        let small = Tree::parse("(a)").unwrap();
+        let c = tree.index(["c"]).unwrap();
+        assert!(small.get(c.id()).is_none());
+    }
+
+    #[test]
+    fn test_wide_child_lookup() {
+        let names: Vec<_> = (0..100).rev().map(|i| format!("f{i}")).collect();
+        let filter = format!("(a({},f5(x)),{})", names.join(","), names.join(","));
+        let tree = Tree::parse(filter).unwrap();
+        assert_eq!(tree.tree.wide_children.len(), 2);
+        let a = tree.index(["a"]).unwrap();
+        for name in &names {
+            assert_eq!(a.child(name).unwrap().name(), name);
+            assert_eq!(tree.index(["a", name]).unwrap().name(), name);
+            assert_eq!(tree.index([name]).unwrap().name(), name);
+        }
+        assert!(a.child("f100").is_none());
+        assert!(a.child("b").is_none());
+        // The first of several children with the same name is found.
+        assert!(!a.child("f5").unwrap().has_children());
+        assert_eq!(tree.decide(["a", "f5", "y"]), Decision::Include);
+    }
+
+    #[test]
+    fn test_traversals() {
+        let tree = Tree::parse("(a(b(c),d),e)").unwrap();
+        let edges: Vec<_> = tree
+            .traverse()
+            .map(|edge| match edge {
+                Edge::Enter(field) => format!("+{}", field.name()),
+                Edge::Leave(field) => format!("-{}", field.name()),
+            })
+            .collect();
+        assert_eq!(
+            edges,
+            ["+a", "+b", "+c", "-c", "-b", "+d", "-d", "-a", "+e", "-e"]
+        );
+
+        let b = tree.index(["a", "b"]).unwrap();
+        assert_eq!(b.traverse().count(), 4);
+
+        for (depth, field) in tree.walk_with_depth() {
+            assert_eq!(depth, field.depth());
+        }
+        assert!(
+            tree.walk_with_depth()
+                .map(|(_, f)| f.id())
+                .eq(tree.walk().map(|f| f.id()))
+        );
+
+        let names: Vec<_> = tree.breadth_first().map(|f| f.name()).collect();
+        assert_eq!(names, ["a", "e", "b", "d", "c"]);
+        let names: Vec<_> = tree.post_order().map(|f| f.name()).collect();
+        assert_eq!(names, ["c", "b", "d", "a", "e"]);
+    }
+
+    #[test]
+    fn test_spans() {
+        let s = "!(a(b(c),d),e(f(g(h))))";
+        let tree = Tree::parse(s).unwrap();
+        for field in tree.walk() {
+            assert_eq!(&s[field.span()], field.name());
+            let substruct = &s[field.substruct_span()];
+            assert!(substruct.starts_with(field.name()));
+            assert_eq!(
+                Tree::parse(format!("({substruct})")).unwrap().top().count(),
+                1
+            );
+        }
+        assert_eq!(&s[tree.index(["a"]).unwrap().substruct_span()], "a(b(c),d)");
+        assert_eq!(
+            &s[tree.index(["e"]).unwrap().substruct_span()],
+            "e(f(g(h)))"
+        );
+        assert_eq!(&s[tree.index(["a", "d"]).unwrap().substruct_span()], "d");
+
+        let mut tree = tree;
+        tree.insert_path(["a", "b", "x"]).unwrap();
+        let b = tree.index(["a", "b"]).unwrap();
+        assert_eq!(&tree.to_string()[b.substruct_span()], "b(c,x)");
+    }
+
+    #[test]
+    fn test_attach_verifies_buffer() {
+        let (buffer, detached) = Tree::parse("!(a(b),c)").unwrap().detach();
+        let tree = detached.clone().attach(buffer.into_owned()).unwrap();
+        assert_eq!(tree.index(["a", "b"]).unwrap().span(), 4..5);
+        for other in ["!(a(b),d)", "!(a(b))", ""] {
+            let Err(error) = detached.clone().attach(other) else {
+                panic!("{other:?} differs from the parsed string");
+            };
+            assert_eq!(error.buffer, other);
+            error.tree.attach("!(a(b),c)").unwrap();
+        }
+
+        let mut tree = Tree::parse("(a)").unwrap();
+        tree.insert_path(["b"]).unwrap();
+        let (buffer, detached) = tree.detach();
+        assert_eq!(detached.attach(buffer).unwrap().walk().count(), 2);
+        let (_, detached) = Tree::parse_detached("(a)")
+            .unwrap()
+            .attach("(a)")
+            .unwrap()
+            .detach();
+        assert!(detached.attach("(b)").is_err());
+    }
 }
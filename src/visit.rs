@@ -0,0 +1,176 @@
+//! This module contains the [`Visit`] trait for walking a [`Tree`] with
+//! hooks for fields, leaves and nested structs.
+
+use crate::tree::Edge;
+use crate::tree::Field;
+use crate::tree::Tree;
+
+/// Hooks which are called by [`Tree::accept`] while walking the fields of a
+/// tree depth-first.
+///
+/// For every field, [`visit_field`](Visit::visit_field) is called first. A
+/// field without children is then passed to [`visit_leaf`](Visit::visit_leaf).
+/// A field with children is passed to [`enter_struct`](Visit::enter_struct),
+/// then its children are visited, and finally it is passed to
+/// [`leave_struct`](Visit::leave_struct).
+///
+/// All hooks do nothing by default.
+///
+/// # Example
+///
+/// ```
+/// use z157::Field;
+/// use z157::Tree;
+/// use z157::Visit;
+/// use z157::VisitContext;
+///
+/// /// Collects the columns selected by an allowlist.
+/// struct Columns(Vec<String>);
+///
+/// impl Visit<'_> for Columns {
+///     fn visit_leaf(
+///         &mut self,
+///         field: &Field<'_>,
+///         context: &VisitContext,
+///     ) {
+///         if !context.negation() {
+///             self.0.push(field.path().join("_"));
+///         }
+///     }
+/// }
+///
+/// let tree =
+///     Tree::parse("(name,address(city,zip))").unwrap();
+/// let mut columns = Columns(Vec::new());
+/// tree.accept(&mut columns);
+/// assert_eq!(
+///     columns.0,
+///     ["name", "address_city", "address_zip"]
+/// );
+/// ```
+pub trait Visit<'p> {
+    /// Called for every field, before any other hook for that field.
+    fn visit_field(&mut self, field: &Field<'p>, context: &VisitContext) {
+        let _ = (field, context);
+    }
+
+    /// Called for every field which does not have children.
+    fn visit_leaf(&mut self, field: &Field<'p>, context: &VisitContext) {
+        let _ = (field, context);
+    }
+
+    /// Called for every field which has children, before visiting them.
+    fn enter_struct(&mut self, field: &Field<'p>, context: &VisitContext) {
+        let _ = (field, context);
+    }
+
+    /// Called for every field which has children, after visiting them.
+    fn leave_struct(&mut self, field: &Field<'p>, context: &VisitContext) {
+        let _ = (field, context);
+    }
+}
+
+/// Describes where a [`Visit`]ed field is found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VisitContext {
+    negation: bool,
+    depth: usize,
+}
+
+impl VisitContext {
+    /// Whether the visited tree is a denylist rather than an allowlist. See
+    /// [`Tree::negation`].
+    #[must_use]
+    pub fn negation(&self) -> bool {
+        self.negation
+    }
+
+    /// The depth of the visited field. Top-level fields have depth 0. See
+    /// [`Field::depth`].
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+impl Tree<'_> {
+    /// Walk all fields depth-first and call the hooks of `visitor` for each.
+    ///
+    /// See [`Visit`] for the order of the calls.
+    pub fn accept<'t>(&'t self, visitor: &mut impl Visit<'t>) {
+        let mut context = VisitContext {
+            negation: self.negation(),
+            depth: 0,
+        };
+        for edge in self.traverse() {
+            match edge {
+                Edge::Enter(field) => {
+                    visitor.visit_field(&field, &context);
+                    if field.has_children() {
+                        visitor.enter_struct(&field, &context);
+                        context.depth += 1;
+                    } else {
+                        visitor.visit_leaf(&field, &context);
+                    }
+                }
+                Edge::Leave(field) => {
+                    if field.has_children() {
+                        context.depth -= 1;
+                        visitor.leave_struct(&field, &context);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records every call as a string.
+    struct Recorder<'p>(Vec<String>, Vec<&'p str>);
+
+    impl<'p> Visit<'p> for Recorder<'p> {
+        fn visit_field(&mut self, field: &Field<'p>, context: &VisitContext) {
+            self.0
+                .push(format!("field {} {}", field.name(), context.depth()));
+            self.1.push(field.name());
+        }
+
+        fn visit_leaf(&mut self, field: &Field<'p>, context: &VisitContext) {
+            self.0
+                .push(format!("leaf {} {}", field.name(), context.negation()));
+        }
+
+        fn enter_struct(&mut self, field: &Field<'p>, context: &VisitContext) {
+            self.0
+                .push(format!("enter {} {}", field.name(), context.depth()));
+        }
+
+        fn leave_struct(&mut self, field: &Field<'p>, context: &VisitContext) {
+            self.0
+                .push(format!("leave {} {}", field.name(), context.depth()));
+        }
+    }
+
+    #[test]
+    fn test_accept() {
+        let tree = Tree::parse("!(a(b),c)").unwrap();
+        let mut recorder = Recorder(Vec::new(), Vec::new());
+        tree.accept(&mut recorder);
+        assert_eq!(
+            recorder.0,
+            [
+                "field a 0",
+                "enter a 0",
+                "field b 1",
+                "leaf b true",
+                "leave a 0",
+                "field c 0",
+                "leaf c true",
+            ]
+        );
+        assert_eq!(recorder.1, ["a", "b", "c"]);
+    }
+}